@@ -0,0 +1,259 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::fill;
+
+/// Byte range `start..end` in the template content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Text of the span in the template content
+    pub fn text<'a>(&self, content: &'a str) -> &'a str {
+        &content[self.start..self.end]
+    }
+}
+
+/// Parsed template, or the body of a block tag.
+/// Lines are parsed as they are filled, the line which only contains tags, env defines or comments
+/// is not filled as an empty line, and the indent in the body of tag is relative to `min_indent_width`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Ast {
+    /// Env defines of the scope, like `{$ join_with = , $}`
+    pub envs: Vec<EnvNode>,
+    pub lines: Vec<Line>,
+    /// Minimum display width of the indent of lines, None if no line
+    pub min_indent_width: Option<usize>,
+}
+
+/// Env define, like `{$ key = value $}`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EnvNode {
+    pub span: Span,
+    pub key: String,
+    /// Raw value, the unicode escapes and placeholders are not filled
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Line {
+    /// Whitespaces at the start of line, they may be separated by env defines or comments
+    pub indent: Vec<Span>,
+    /// Display width (columns) of the indent
+    pub indent_width: usize,
+    pub nodes: Vec<Node>,
+    /// "\n" or "\r\n", None if the line is the end of template or tag body
+    pub line_feed: Option<String>,
+    /// Count of env defines in the line
+    pub env_count: usize,
+    /// Count of comments in the line
+    pub comment_count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Node {
+    /// Span of the whole symbol, like `{{ name }}`, it is the text itself for text
+    pub span: Span,
+    /// The node is the first visible node in its line, and it is indented by the line
+    pub first_in_line: bool,
+    #[serde(flatten)]
+    pub kind: NodeKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NodeKind {
+    /// Text, and the raw content of `{# ... #}`
+    Text {
+        text: String,
+    },
+    /// Placeholder like `{{ name | indent }}`, the expression is the content without delimiters
+    Placeholder {
+        expression: String,
+    },
+    Tag(TagNode),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TagNode {
+    pub tag: Tag,
+    /// Span of the end tag, like `{% endfor %}`. None if the tag is inline,
+    /// or it is an arm of match which is ended by the next arm
+    pub end_span: Option<Span>,
+    /// Body of the block tag, None if the tag is inline
+    pub body: Option<Ast>,
+}
+
+/// Expression in tag, like `table.name`, `"abc"`, `123` or `true`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Expression {
+    pub kind: ExpressionKind,
+    /// Raw text, the string is quoted
+    pub text: String,
+}
+
+impl Expression {
+    /// Parse the expression, None if it is not valid
+    pub fn parse(text: &str) -> Option<Self> {
+        fill::parse_expression(text)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpressionKind {
+    Variable,
+    String,
+    Number,
+    Boolean,
+}
+
+/// Condition of the tags `if` and `assert`,
+/// the operator is `==`, `!=`, `is defined` or `is not defined` (which has no right)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Condition {
+    pub left: Expression,
+    pub operator: String,
+    pub right: Option<Expression>,
+}
+
+/// Macro parameter, like `name` or `nullable=true`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MacroParam {
+    pub name: String,
+    pub default: Option<Expression>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Tag {
+    For {
+        item: String,
+        /// Name of the value of `for key, value in object`
+        #[serde(skip_serializing_if = "Option::is_none")]
+        value: Option<String>,
+        array: String,
+        recursive: bool,
+    },
+    If {
+        condition: Condition,
+    },
+    Include {
+        template: Expression,
+        with: Option<String>,
+    },
+    Extends {
+        template: Expression,
+    },
+    Block {
+        name: String,
+    },
+    Macro {
+        name: String,
+        params: Vec<MacroParam>,
+    },
+    Import {
+        template: Expression,
+        alias: Option<String>,
+    },
+    Set {
+        name: String,
+    },
+    Match {
+        subject: Expression,
+    },
+    Case {
+        values: Vec<Expression>,
+    },
+    Default,
+    /// `with pk = table.primary_key`, the bindings are (name, expression)
+    With {
+        bindings: Vec<(String, String)>,
+    },
+    /// `with table`, only the data of variable is visible
+    WithData {
+        variable: String,
+    },
+    File {
+        path: Expression,
+    },
+    Assert {
+        condition: Condition,
+        message: Expression,
+    },
+    Error {
+        message: Expression,
+    },
+    /// Tag registered by `FillOptions::custom_tags`, the args are parsed by `CustomTag::parse`
+    Custom {
+        tag: String,
+        args: Value,
+    },
+}
+
+/// Visit the AST in the order of template, the default methods walk into the children
+pub trait Visitor {
+    fn visit_ast(&mut self, ast: &Ast) {
+        walk_ast(self, ast);
+    }
+
+    fn visit_env(&mut self, _env: &EnvNode) {}
+
+    fn visit_line(&mut self, line: &Line) {
+        walk_line(self, line);
+    }
+
+    fn visit_node(&mut self, node: &Node) {
+        walk_node(self, node);
+    }
+
+    fn visit_text(&mut self, _span: Span, _text: &str) {}
+
+    fn visit_placeholder(&mut self, _span: Span, _expression: &str) {}
+
+    fn visit_tag(&mut self, _span: Span, tag_node: &TagNode) {
+        walk_tag(self, tag_node);
+    }
+}
+
+pub fn walk_ast<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast) {
+    for env in &ast.envs {
+        visitor.visit_env(env);
+    }
+    for line in &ast.lines {
+        visitor.visit_line(line);
+    }
+}
+
+pub fn walk_line<V: Visitor + ?Sized>(visitor: &mut V, line: &Line) {
+    for node in &line.nodes {
+        visitor.visit_node(node);
+    }
+}
+
+pub fn walk_node<V: Visitor + ?Sized>(visitor: &mut V, node: &Node) {
+    match &node.kind {
+        NodeKind::Text { text } => visitor.visit_text(node.span, text),
+        NodeKind::Placeholder { expression } => visitor.visit_placeholder(node.span, expression),
+        NodeKind::Tag(tag_node) => visitor.visit_tag(node.span, tag_node),
+    }
+}
+
+pub fn walk_tag<V: Visitor + ?Sized>(visitor: &mut V, tag_node: &TagNode) {
+    if let Some(body) = &tag_node.body {
+        visitor.visit_ast(body);
+    }
+}
+
+impl Ast {
+    pub fn accept<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        visitor.visit_ast(self);
+    }
+}
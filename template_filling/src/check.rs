@@ -0,0 +1,125 @@
+use std::fmt::{self, Display};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    ast::{Span, Visitor},
+    fill::{self, LintProblem},
+    options::FillOptions,
+    vars,
+};
+
+/// Problem of the template which is found by `check`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// Span of the symbol where the problem is, like `{% endfor %}`
+    pub span: Span,
+    /// Line of the symbol, start at 1
+    pub line: usize,
+    /// Column of the symbol in chars, start at 1
+    pub column: usize,
+    /// How to fix the problem, like "Did you mean `endif`?"
+    pub hint: Option<String>,
+    /// Name of the included template where the problem is, None if it is in the filled template
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+}
+
+/// Error if the filling fails with the problem, warning if the filling tolerates it,
+/// like the unclosed tag which is dropped or the undefined variable which is filled as not found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+impl Diagnostic {
+    pub(crate) fn new(template_content: &str, problem: LintProblem) -> Self {
+        let (start, end) = problem.span;
+        let (line, column) = fill::line_column(template_content.as_bytes(), start);
+        Diagnostic {
+            severity: problem.severity,
+            message: problem.message,
+            span: Span::new(start, end),
+            line,
+            column,
+            hint: problem.hint,
+            template: None,
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(template) = &self.template {
+            write!(f, "{}:", template)?;
+        }
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// Check the template without filling it, the problems are unbalanced or mismatched tags,
+/// unknown tags, malformed expressions and unterminated delimiters.
+/// The variables which can not be found in the data are reported if the data is given.
+/// The included, extended and imported templates are not checked
+/// @return Problems in the order of template, empty if the template is fine
+pub fn check<T: AsRef<str>>(
+    template_content: T,
+    data_opt: Option<&Value>,
+    options: &FillOptions,
+) -> Vec<Diagnostic> {
+    let template_content = template_content.as_ref();
+    let (template_ast, problems) = fill::lint_template(template_content, options);
+    let mut diagnostics: Vec<Diagnostic> = problems
+        .into_iter()
+        .map(|problem| Diagnostic::new(template_content, problem))
+        .collect();
+    let mut checker = PlaceholderChecker {
+        template_content,
+        diagnostics: Vec::new(),
+    };
+    template_ast.accept(&mut checker);
+    diagnostics.append(&mut checker.diagnostics);
+    if let Some(data) = data_opt {
+        for (path, span) in vars::collect_variables(&template_ast, options) {
+            if !vars::is_path_defined(data, &path) {
+                let problem = LintProblem {
+                    message: format!("Undefined variable: {}", path),
+                    span: (span.start, span.end),
+                    severity: Severity::Warning,
+                    hint: Some(format!("Add `{}` to the data", path)),
+                };
+                diagnostics.push(Diagnostic::new(template_content, problem));
+            }
+        }
+    }
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+    diagnostics
+}
+
+struct PlaceholderChecker<'a> {
+    template_content: &'a str,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Visitor for PlaceholderChecker<'_> {
+    fn visit_placeholder(&mut self, span: Span, expression: &str) {
+        if let Some(problem) = fill::check_placeholder(expression, (span.start, span.end)) {
+            self.diagnostics
+                .push(Diagnostic::new(self.template_content, problem));
+        }
+    }
+}
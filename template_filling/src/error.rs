@@ -0,0 +1,25 @@
+use std::fmt::{self, Display};
+
+/// Error which aborts the filling, like the failed tag 'assert' or the tag 'error'
+#[derive(Debug, Clone, PartialEq)]
+pub struct FillError {
+    pub message: String,
+    /// Name of the included template where the error is, None if it is in the filled template
+    pub template: Option<String>,
+    /// Line of the tag, start at 1
+    pub line: usize,
+    /// Column of the tag in chars, start at 1
+    pub column: usize,
+}
+
+impl Display for FillError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, at ", self.message)?;
+        if let Some(template) = &self.template {
+            write!(f, "{}:", template)?;
+        }
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+impl std::error::Error for FillError {}
@@ -3,40 +3,241 @@ use std::{cell::RefCell, collections::HashMap, rc::Rc, str};
 use chrono::Local;
 use serde_json::{json, Value};
 
-use crate::tpd::unicode_escape;
+use crate::{
+    ast::{self, Span},
+    check::{Diagnostic, Severity},
+    error::FillError,
+    options::{FillOptions, IndentStyle, LineEnding, TrailingNewline},
+    report::{LoopStat, RenderReport, ReportState},
+    source_map::{Filled, SourceMap, SourceMapState, TokenOrigin},
+    syntax::{Syntax, SYNTAX_DIRECTIVE},
+    tag::CustomTag,
+    tpd::unicode_escape,
+};
 
 pub fn fill_template<T: AsRef<str>>(template_content: T, data_opt: Option<&Value>) -> String {
-    let data = data_opt.unwrap_or_else(|| &Value::Null);
-    // Generate tokens
+    fill_template_with_options(template_content, data_opt, &FillOptions::default())
+}
+
+pub fn fill_template_with_options<T: AsRef<str>>(
+    template_content: T,
+    data_opt: Option<&Value>,
+    options: &FillOptions,
+) -> String {
+    try_fill_template_with_options(template_content, data_opt, options)
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Fill the template, it is failed by the tags 'assert' and 'error'
+pub fn try_fill_template_with_options<T: AsRef<str>>(
+    template_content: T,
+    data_opt: Option<&Value>,
+    options: &FillOptions,
+) -> Result<String, FillError> {
+    try_fill_template_with_mode(template_content, data_opt, options, &RenderMode::default())
+        .map(|output| output.output)
+}
+
+/// Fill the template and get the content of the first block named `block_name`,
+/// it is the same as the block content in the whole filled result
+pub fn fill_template_block<T: AsRef<str>>(
+    template_content: T,
+    block_name: &str,
+    data_opt: Option<&Value>,
+    options: &FillOptions,
+) -> Option<String> {
+    try_fill_template_block(template_content, block_name, data_opt, options)
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn try_fill_template_block<T: AsRef<str>>(
+    template_content: T,
+    block_name: &str,
+    data_opt: Option<&Value>,
+    options: &FillOptions,
+) -> Result<Option<String>, FillError> {
+    let mode = RenderMode {
+        block: Some(block_name.to_owned()),
+        ..RenderMode::default()
+    };
+    try_fill_template_with_mode(template_content, data_opt, options, &mode)
+        .map(|output| output.block)
+}
+
+/// Fill the template which emits files by the tag 'file'
+/// @return (filled content out of the tag 'file', [(file path, file content)])
+pub fn fill_template_files<T: AsRef<str>>(
+    template_content: T,
+    data_opt: Option<&Value>,
+    options: &FillOptions,
+) -> (String, Vec<(String, String)>) {
+    try_fill_template_files(template_content, data_opt, options).unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn try_fill_template_files<T: AsRef<str>>(
+    template_content: T,
+    data_opt: Option<&Value>,
+    options: &FillOptions,
+) -> Result<(String, Vec<(String, String)>), FillError> {
+    try_fill_template_with_mode(template_content, data_opt, options, &RenderMode::default())
+        .map(|output| (output.output, output.files))
+}
+
+/// Fill the template and keep going past the recoverable problems, like the undefined variables,
+/// the illegal filters, the invalid escapes and the templates which can not be loaded.
+/// The marker like `{{Error: message}}` is filled at each failed point,
+/// and the tags 'assert' and 'error' are recorded as errors instead of aborting the filling
+/// @return (filled content, problems in the order of filling)
+pub fn fill_template_with_diagnostics<T: AsRef<str>>(
+    template_content: T,
+    data_opt: Option<&Value>,
+    options: &FillOptions,
+) -> (String, Vec<Diagnostic>) {
+    let mode = RenderMode {
+        recover: true,
+        ..RenderMode::default()
+    };
+    let output = try_fill_template_with_mode(template_content, data_opt, options, &mode)
+        .expect("The filling is not aborted in the recovery mode");
+    (output.output, output.diagnostics)
+}
+
+/// Fill the template with what is recorded besides the output, the records can be combined.
+/// It is failed by the tags 'assert' and 'error' unless it is in the recovery mode
+pub fn try_fill_template_with_mode<T: AsRef<str>>(
+    template_content: T,
+    data_opt: Option<&Value>,
+    options: &FillOptions,
+    mode: &RenderMode,
+) -> Result<RenderOutput, FillError> {
+    fill_template_0(template_content.as_ref(), data_opt, options, mode)
+}
+
+/// Parse the template into the AST, it is the same as the filling uses
+pub fn parse_template<T: AsRef<str>>(template_content: T, options: &FillOptions) -> ast::Ast {
     let bytes = template_content.as_ref().as_bytes();
-    let template_ast = generate_tokens(bytes);
+    to_public_ast(bytes, &generate_tokens(bytes, options))
+}
+
+/// What is captured or recorded besides the filled output, default is nothing
+#[derive(Debug, Clone, Default)]
+pub struct RenderMode {
+    /// Name of the block which content is captured when it is filled first
+    pub block: Option<String>,
+    /// Keep going past the recoverable problems and collect them as diagnostics
+    pub recover: bool,
+    /// Report the usage of the data
+    pub report: bool,
+    /// Map the output to the template lines which produce it
+    pub source_map: bool,
+}
+
+/// Filled output and what is recorded by the `RenderMode`
+#[derive(Debug, Clone, Default)]
+pub struct RenderOutput {
+    /// Filled content out of the tag 'file'
+    pub output: String,
+    /// Content of the block named by `RenderMode::block`, None if it is not filled
+    pub block: Option<String>,
+    /// [(file path, file content)] of the files emitted by the tag 'file'
+    pub files: Vec<(String, String)>,
+    /// Problems in the order of filling, they are only collected in the recovery mode
+    pub diagnostics: Vec<Diagnostic>,
+    /// Usage of the data if it is reported
+    pub report: Option<RenderReport>,
+    /// Origins of the block content if the block is captured, otherwise of the output
+    pub source_map: Option<SourceMap>,
+}
+
+fn fill_template_0(
+    template_content: &str,
+    data_opt: Option<&Value>,
+    options: &FillOptions,
+    mode: &RenderMode,
+) -> Result<RenderOutput, FillError> {
+    let data = data_opt.unwrap_or(&Value::Null);
+    let mut data_ctx = AutoDataContext::new(data, options);
+    if mode.recover {
+        data_ctx.recovery = Some(Rc::new(RefCell::new(Recovery::default())));
+    }
+    if mode.report {
+        data_ctx.report = Some(Rc::new(RefCell::new(ReportState::default())));
+    }
+    if mode.source_map {
+        data_ctx.source_map = Some(Rc::new(RefCell::new(SourceMapState::default())));
+    }
+    // Generate tokens
+    let bytes = template_content.as_bytes();
+    let template_ast = parse_template_in(&mut data_ctx, bytes, None);
+    if let Some(error) = data_ctx.error {
+        return Err(error);
+    }
     // Debug
     if cfg!(debug_assertions) && cfg!(not(test)) {
         println!("{:?}", template_ast);
     }
     // Fill with token
-    fill(
-        bytes,
-        &template_ast,
-        &mut AutoDataContext::new(data),
-        false,
-        true,
-    )
+    data_ctx.captured_block = mode.block.clone().map(|name| (name, None));
+    let filled = fill_template_tree(bytes, template_ast, &mut data_ctx, false);
+    // The failure out of tokens (for example the parent template which can not be loaded)
+    // is located at the start of template
+    data_ctx.locate_error(bytes, 0);
+    if let Some(error) = data_ctx.error {
+        return Err(error);
+    }
+    // The problems out of tokens (for example the parent template which can not be loaded)
+    // are located at the start of template
+    data_ctx.locate_problems(bytes, (0, 0));
+    let diagnostics = data_ctx
+        .recovery
+        .map(|recovery| recovery.take().diagnostics)
+        .unwrap_or_default();
+    let report = data_ctx.report.map(|report| report.take().finish(data));
+    // Format output
+    let format =
+        |filled: Filled| normalize_line_feed(bytes, convert_indent(filled, options), options);
+    let filled = format(filled);
+    let block = data_ctx
+        .captured_block
+        .and_then(|(_, captured)| captured)
+        .map(format);
+    let source_map = data_ctx.source_map.map(|source_map| {
+        let source_map = source_map.take();
+        match (&mode.block, &block) {
+            (Some(_), Some(block)) => source_map.extract(block),
+            (Some(_), None) => SourceMap::default(),
+            (None, _) => source_map.extract(&filled),
+        }
+    });
+    Ok(RenderOutput {
+        output: filled.text,
+        block: block.map(|block| block.text),
+        files: data_ctx
+            .files
+            .into_iter()
+            .map(|(path, content)| (path, format(content.into()).text))
+            .collect(),
+        diagnostics,
+        report,
+        source_map,
+    })
 }
 
 /// Template Abstract Syntax Table
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct TemplateASTable {
     is_tag: bool,
     has_line: bool,
     current_line: Option<SyntaxLine>,
     custom_envs: Vec<EnvDefine>,
     syntax_lines: Vec<SyntaxLine>,
+    /// Display width (columns) of the minimum indent
     min_indent_len: Option<usize>,
+    tab_width: usize,
 }
 
 impl TemplateASTable {
-    pub fn new(is_tag: bool) -> Self {
+    pub fn new(is_tag: bool, tab_width: usize) -> Self {
         let mut sf = Self {
             is_tag,
             has_line: false,
@@ -44,6 +245,7 @@ impl TemplateASTable {
             custom_envs: Vec::new(),
             syntax_lines: Vec::new(),
             min_indent_len: None,
+            tab_width,
         };
         sf.new_line(None);
         sf
@@ -56,6 +258,11 @@ impl TemplateASTable {
         self.custom_envs.push(env_define);
     }
 
+    pub fn push_comment(&mut self) {
+        let line = self.current_line.as_mut().expect("No line can be found");
+        line.comment_cnt += 1;
+    }
+
     /// Execute this function when line finished
     pub fn new_line(&mut self, currnet_line_line_feed: Option<LineFeed>) {
         // push last line
@@ -71,7 +278,7 @@ impl TemplateASTable {
     fn push_line(&mut self, is_finish: bool, currnet_line_line_feed: Option<LineFeed>) {
         if let Some(mut current_line) = self.current_line.take() {
             if self.should_push_line(&current_line, is_finish) {
-                if current_line.tokens.len() > 0 {
+                if !current_line.tokens.is_empty() {
                     match current_line.tokens.last_mut().unwrap() {
                         Token::Text(token_ctx)
                         | Token::Placeholder(token_ctx)
@@ -95,7 +302,7 @@ impl TemplateASTable {
     fn should_push_line(&self, line: &SyntaxLine, is_finish: bool) -> bool {
         if self.is_tag {
             if is_finish {
-                line.tokens.len() > 0
+                !line.tokens.is_empty()
             } else {
                 // First Line in Tag
                 self.has_line || line.is_valid_line() && !line.is_empty_line()
@@ -107,24 +314,47 @@ impl TemplateASTable {
 
     pub fn push_token(&mut self, template_bytes: &[u8], token: Token) {
         let line = self.current_line.as_mut().expect("No line can be found");
-        line.push_token(template_bytes, token);
+        line.push_token(template_bytes, token, self.tab_width);
+    }
+
+    /// Nothing (include indent) has been pushed into current line
+    pub fn is_blank_line_now(&self) -> bool {
+        self.current_line
+            .as_ref()
+            .is_some_and(|line| line.indent.is_none() && line.is_empty_line())
+    }
+
+    /// No visible token (the indent is not) has been pushed into current line
+    pub fn is_empty_line_now(&self) -> bool {
+        self.current_line
+            .as_ref()
+            .is_none_or(|line| line.is_empty_line())
+    }
+
+    pub fn is_tag_last_now(&self) -> bool {
+        self.current_line
+            .as_ref()
+            .is_some_and(|line| matches!(line.tokens.last(), Some(Token::Tag(..))))
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct SyntaxLine {
     /// Vec<(indent_index_start, indent_index_end)>
     indent: Option<Vec<(usize, usize)>>,
+    /// Display width (columns) of the indent
     pub indent_len: usize,
     pub tokens: Vec<Token>,
     pub line_feed: Option<LineFeed>,
     pub env_define_cnt: usize,
+    pub comment_cnt: usize,
     pub text_token_cnt: usize,
     pub placeholder_token_cnt: usize,
     pub tag_token_cnt: usize,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[allow(clippy::upper_case_acronyms)]
 enum LineFeed {
     /// \n
     LF,
@@ -140,16 +370,17 @@ impl SyntaxLine {
             tokens: Vec::new(),
             line_feed: None,
             env_define_cnt: 0,
+            comment_cnt: 0,
             text_token_cnt: 0,
             placeholder_token_cnt: 0,
             tag_token_cnt: 0,
         }
     }
 
-    pub fn push_token(&mut self, template_bytes: &[u8], mut token: Token) {
+    pub fn push_token(&mut self, template_bytes: &[u8], mut token: Token, tab_width: usize) {
         match token {
             Token::Text(ref mut token_ctx) => {
-                if self.visible_token_count() <= 0 {
+                if self.visible_token_count() == 0 {
                     let start = token_ctx.start;
                     let end = token_ctx.end;
                     let text = bytes_to_str(template_bytes, start, end);
@@ -162,15 +393,17 @@ impl SyntaxLine {
                         let non_blank_len = non_blank_text.len();
                         // Indent + text line
                         if non_blank_len < text.len() {
-                            self.push_indent(start, start + (text.len() - non_blank_len));
+                            let indent_end = start + (text.len() - non_blank_len);
+                            self.push_indent(template_bytes, start, indent_end, tab_width);
                             token_ctx.start = end - non_blank_len;
+                            token_ctx.head_start = token_ctx.start;
                         }
                         self.tokens.push(token);
                         self.text_token_cnt += 1;
                     }
                     // Blank text
                     else {
-                        self.push_indent(start, end);
+                        self.push_indent(template_bytes, start, end, tab_width);
                     }
                 } else {
                     self.tokens.push(token);
@@ -178,14 +411,14 @@ impl SyntaxLine {
                 }
             }
             Token::Tag(ref mut token_ctx, _) => {
-                if self.visible_token_count() <= 0 {
+                if self.visible_token_count() == 0 {
                     token_ctx.first_in_line = true;
                 }
                 self.tokens.push(token);
                 self.tag_token_cnt += 1;
             }
             Token::Placeholder(ref mut token_ctx) => {
-                if self.visible_token_count() <= 0 {
+                if self.visible_token_count() == 0 {
                     token_ctx.first_in_line = true;
                 }
                 self.tokens.push(token);
@@ -194,7 +427,13 @@ impl SyntaxLine {
         }
     }
 
-    fn push_indent(&mut self, indent_start: usize, indent_end: usize) {
+    fn push_indent(
+        &mut self,
+        template_bytes: &[u8],
+        indent_start: usize,
+        indent_end: usize,
+        tab_width: usize,
+    ) {
         if self.indent.is_none() {
             self.indent = Some(Vec::new());
         }
@@ -202,16 +441,13 @@ impl SyntaxLine {
             .as_mut()
             .unwrap()
             .push((indent_start, indent_end));
-        self.indent_len += indent_end - indent_start;
+        let indent = bytes_to_str(template_bytes, indent_start, indent_end);
+        self.indent_len = indent_columns(self.indent_len, indent, tab_width);
     }
 
     pub fn get_indent(&self, template_bytes: &[u8]) -> Option<String> {
-        if self.indent.is_none() {
-            return None;
-        }
-
         let mut indent = String::new();
-        for (start, end) in self.indent.as_ref().unwrap() {
+        for (start, end) in self.indent.as_ref()? {
             indent.push_str(bytes_to_str(template_bytes, *start, *end));
         }
         Some(indent)
@@ -222,52 +458,66 @@ impl SyntaxLine {
     }
 
     pub fn is_valid_line(&self) -> bool {
-        // Will not fill line feed when only Token::Env in line
-        self.visible_token_count() > 0 || self.env_define_cnt == 0
+        // Will not fill line feed when only Token::Env or comment in line
+        self.visible_token_count() > 0 || self.env_define_cnt == 0 && self.comment_cnt == 0
     }
 
     pub fn is_empty_line(&self) -> bool {
-        self.visible_token_count() == 0 && self.tag_token_cnt == 0 && self.env_define_cnt == 0
+        self.visible_token_count() == 0
+            && self.tag_token_cnt == 0
+            && self.env_define_cnt == 0
+            && self.comment_cnt == 0
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum Symbol {
     Logical,
     Env,
     Placeholder,
     Raw,
+    Comment,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct EnvDefine {
     start: usize,
     end: usize,
+    /// (head_start, tail_end) of the symbol, like '{$ key = value $}'
+    span: (usize, usize),
 }
 
 impl EnvDefine {
-    pub fn new(start: usize, end: usize) -> Self {
-        EnvDefine { start, end }
+    pub fn new(start: usize, end: usize, span: (usize, usize)) -> Self {
+        EnvDefine { start, end, span }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct TokenContext {
     start: usize,
     end: usize,
+    /// Start index of the symbol head (for example '{%'), it is the same as start for text
+    head_start: usize,
+    /// End index of the symbol tail (for example '%}'), it is the same as end for text
+    tail_end: usize,
     /// Determine by checking the 'tag_token_stack' in GenerateTokensContext, false if empty
     in_tag: bool,
     first_in_line: bool,
     end_of_line: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct TagExtend {
     tag: Tag,
     sub_ast: TemplateASTable,
+    /// (head_start, tail_end) of the end tag, like '{% endfor %}'. None if the tag is inline,
+    /// or it is an arm of match which is ended by the next arm
+    end_tag_span: Option<(usize, usize)>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
 enum Token {
     Text(TokenContext),
     Placeholder(TokenContext),
@@ -279,6 +529,8 @@ impl Token {
         Token::Text(TokenContext {
             start,
             end,
+            head_start: start,
+            tail_end: end,
             in_tag: ctx.now_in_tag(),
             first_in_line: false,
             end_of_line: false,
@@ -289,6 +541,8 @@ impl Token {
         Token::Placeholder(TokenContext {
             start,
             end,
+            head_start: ctx.last_symbol_head_pos,
+            tail_end: ctx.last_symbol_tail_end,
             in_tag: ctx.now_in_tag(),
             first_in_line: false,
             end_of_line: false,
@@ -300,13 +554,16 @@ impl Token {
             TokenContext {
                 start,
                 end,
+                head_start: ctx.last_symbol_head_pos,
+                tail_end: ctx.last_symbol_tail_end,
                 in_tag: ctx.now_in_tag(),
                 first_in_line: false,
                 end_of_line: false,
             },
             TagExtend {
                 tag,
-                sub_ast: TemplateASTable::new(true),
+                sub_ast: TemplateASTable::new(true, ctx.tab_width),
+                end_tag_span: None,
             },
         )
     }
@@ -314,36 +571,90 @@ impl Token {
 
 struct GenerateTokensContext {
     pub last_start_pos: usize,
+    pub last_symbol_head_pos: usize,
+    pub last_symbol_tail_end: usize,
     pub template_ast: TemplateASTable,
 
     // <<< Keep coding, time will reward --- 2025/5/22 1:01 >>>
-    pub now_in_raw: bool,
     pub now_has_first_non_blank: bool,
     /// Index start and end of indent text.
     /// There may be multiple separated whitespace characters,
     /// for example (The * symbol stands for whitespace characters),
-    /// ```
+    /// ```text
     /// *******<$ custom_env = 123 $>***
     /// ```
     pub indent_in_line: Vec<(usize, usize)>,
 
     pub head_symbol_stack: Vec<(Symbol, usize)>,
     pub tag_token_stack: Vec<Token>,
+    pub tab_width: usize,
+    pub custom_tags: Vec<Rc<dyn CustomTag>>,
+    /// Problems found in lint mode, None if not linting and the problem panics
+    pub problems: Option<Vec<LintProblem>>,
+    /// (depth of tag_token_stack, tag name) of the illegal head tags which are dropped in lint mode,
+    /// their end tags are dropped too
+    pub illegal_heads: Vec<(usize, String)>,
+}
+
+/// Problem which is found in lint mode
+#[derive(Debug)]
+pub(crate) struct LintProblem {
+    pub message: String,
+    /// (head_start, tail_end) of the symbol where the problem is
+    pub span: (usize, usize),
+    /// Error if the filling panics with the problem, warning if it is tolerated
+    pub severity: Severity,
+    pub hint: Option<String>,
 }
 
-impl<'a> GenerateTokensContext {
-    fn new() -> Self {
+impl GenerateTokensContext {
+    fn new(tab_width: usize, custom_tags: &[Rc<dyn CustomTag>], lint: bool) -> Self {
         Self {
             last_start_pos: 0,
-            template_ast: TemplateASTable::new(false),
-            now_in_raw: false,
+            last_symbol_head_pos: 0,
+            last_symbol_tail_end: 0,
+            template_ast: TemplateASTable::new(false, tab_width),
             now_has_first_non_blank: false,
             indent_in_line: Vec::new(),
             head_symbol_stack: Vec::with_capacity(1),
             tag_token_stack: Vec::new(),
+            tab_width,
+            custom_tags: custom_tags.to_vec(),
+            problems: lint.then(Vec::new),
+            illegal_heads: Vec::new(),
         }
     }
 
+    /// Record the problem in lint mode, otherwise panic
+    fn fail(&mut self, span: (usize, usize), message: String, hint: Option<String>) {
+        match self.problems.as_mut() {
+            Some(problems) => problems.push(LintProblem {
+                message,
+                span,
+                severity: Severity::Error,
+                hint,
+            }),
+            None => panic!("{}", message),
+        }
+    }
+
+    /// Record the problem in lint mode, otherwise it is tolerated by filling
+    fn lint(&mut self, span: (usize, usize), message: String, hint: Option<String>) {
+        if let Some(problems) = self.problems.as_mut() {
+            problems.push(LintProblem {
+                message,
+                span,
+                severity: Severity::Warning,
+                hint,
+            });
+        }
+    }
+
+    /// Span of the symbol which is closed just now
+    fn symbol_span(&self) -> (usize, usize) {
+        (self.last_symbol_head_pos, self.last_symbol_tail_end)
+    }
+
     pub fn push_env(&mut self, env: EnvDefine) {
         if self.now_in_tag() {
             if let Token::Tag(_, TagExtend { sub_ast, .. }) =
@@ -358,6 +669,10 @@ impl<'a> GenerateTokensContext {
         }
     }
 
+    pub fn push_comment(&mut self) {
+        self.current_ast_mut().push_comment();
+    }
+
     pub fn push_token(&mut self, template_bytes: &[u8], mut token: Token) {
         if self.now_in_tag() {
             if let Token::Tag(_, TagExtend { sub_ast, .. }) =
@@ -406,226 +721,741 @@ impl<'a> GenerateTokensContext {
     pub fn now_in_tag(&self) -> bool {
         !self.tag_token_stack.is_empty()
     }
-}
 
-fn generate_tokens(template_bytes: &[u8]) -> TemplateASTable {
-    let mut ctx = GenerateTokensContext::new();
+    fn current_ast(&self) -> &TemplateASTable {
+        if let Some(Token::Tag(_, TagExtend { sub_ast, .. })) = self.tag_token_stack.last() {
+            sub_ast
+        } else {
+            &self.template_ast
+        }
+    }
 
-    let bytes = template_bytes;
-    let mut i = 0;
-    let i_max = bytes.len().saturating_sub(1);
-    while i < i_max {
-        // Raw
-        if ctx.now_in_raw {
-            if bytes[i] == b'#' && bytes[i + 1] == b'}' {
-                if let Some((Symbol::Raw, _)) = ctx.head_symbol_stack.last() {
-                    let (_, start_idx) = ctx.head_symbol_stack.pop().unwrap();
-                    let token = Token::new_text(&mut ctx, start_idx, i);
-                    ctx.push_token(template_bytes, token);
-                    ctx.last_start_pos = i + 2;
-                }
-                i += 2;
-                ctx.now_in_raw = false;
-                continue;
-            } else {
-                i += 1;
-                continue;
-            }
+    fn current_ast_mut(&mut self) -> &mut TemplateASTable {
+        if let Some(Token::Tag(_, TagExtend { sub_ast, .. })) = self.tag_token_stack.last_mut() {
+            sub_ast
+        } else {
+            &mut self.template_ast
         }
+    }
 
-        match (&bytes[i], &bytes[i + 1]) {
-            (b'{', b'%') => {
-                if ctx.last_start_pos < i {
-                    let last_pos = ctx.last_start_pos;
-                    let token = Token::new_text(&mut ctx, last_pos, i);
-                    ctx.push_token(template_bytes, token);
-                    ctx.last_start_pos = i;
+    /// Record the symbol head (for example '{%') which is start at `head_start`,
+    /// and push the text in front of it.
+    /// @return Index after the symbol head
+    pub fn open_symbol(
+        &mut self,
+        template_bytes: &[u8],
+        symbol: Symbol,
+        head_start: usize,
+        head_len: usize,
+        lstrip: bool,
+    ) -> usize {
+        let mut head_end = head_start + head_len;
+        let mut text_end = head_start;
+        // Whitespace control: '{%-' strip the whitespaces before it
+        if template_bytes.get(head_end) == Some(&b'-') {
+            head_end += 1;
+            text_end = trim_end_whitespace(template_bytes, self.last_start_pos, head_start);
+        }
+        // Whitespace control: lstrip_blocks strip the indent before the tag
+        else if lstrip
+            && self.current_ast().is_blank_line_now()
+            && template_bytes[self.last_start_pos..head_start]
+                .iter()
+                .all(|b| *b == b' ' || *b == b'\t')
+        {
+            text_end = self.last_start_pos;
+        }
+        if self.last_start_pos < text_end {
+            let token = Token::new_text(self, self.last_start_pos, text_end);
+            self.push_token(template_bytes, token);
+        }
+        self.head_symbol_stack.push((symbol, head_end));
+        self.last_symbol_head_pos = head_start;
+        self.last_start_pos = head_end;
+        head_end
+    }
+
+    /// Head tag (for example 'for') is pushed into tag_token_stack,
+    /// and it is popped as a token when its end tag (for example 'endfor') comes
+    pub fn push_tag(&mut self, template_bytes: &[u8], start_idx: usize, end_idx: usize) {
+        let tag = match generate_tag(&template_bytes[start_idx..end_idx], &self.custom_tags) {
+            Ok(tag) => tag,
+            Err(message) => {
+                // The illegal tag is dropped in lint mode
+                let tag_text = bytes_to_str(template_bytes, start_idx, end_idx);
+                let hint = tag_hint(tag_text, &self.custom_tags);
+                self.fail(self.symbol_span(), message, hint);
+                let name = tag_text.split_whitespace().next().unwrap_or_default();
+                let end_name = format!("end{}", name);
+                if TAG_KEYWORDS.contains(&end_name.as_str())
+                    || self
+                        .custom_tags
+                        .iter()
+                        .any(|tag| tag.name() == name && tag.has_end_tag())
+                {
+                    self.illegal_heads
+                        .push((self.tag_token_stack.len(), name.to_owned()));
                 }
-                ctx.head_symbol_stack.push((Symbol::Logical, i + 2));
-                ctx.last_start_pos += 2;
-                i += 2;
+                return;
             }
-            (b'%', b'}') => {
-                if let Some((Symbol::Logical, _)) = ctx.head_symbol_stack.last() {
-                    let (_, start_idx) = ctx.head_symbol_stack.pop().unwrap();
-                    let tag = generate_tag(&bytes[start_idx..i]);
-                    match tag {
-                        Tag::For(_, _) => {
-                            let token = Token::new_tag(&mut ctx, tag, start_idx, i);
-                            ctx.tag_token_stack.push(token);
-                        }
-                        Tag::EndFor => {
-                            if let Some(mut head_tag_token) = ctx.tag_token_stack.pop() {
-                                if let Token::Tag(
-                                    _,
-                                    TagExtend {
-                                        tag: head_tag,
-                                        sub_ast,
-                                        ..
-                                    },
-                                ) = &mut head_tag_token
-                                {
-                                    match head_tag {
-                                        Tag::For(_, _) => {
-                                            sub_ast.finish_build();
-                                            ctx.push_token(template_bytes, head_tag_token);
-                                        }
-                                        _ => panic!("Tag must be balanced"),
-                                    }
-                                } else {
-                                    panic!("Missing head tag");
-                                }
-                            }
-                        }
-                        Tag::If(..) => {
-                            let token = Token::new_tag(&mut ctx, tag, start_idx, i);
-                            ctx.tag_token_stack.push(token);
-                        }
-                        Tag::EndIf => {
-                            if let Some(mut head_tag_token) = ctx.tag_token_stack.pop() {
-                                if let Token::Tag(
-                                    _,
-                                    TagExtend {
-                                        tag: head_tag,
-                                        sub_ast,
-                                        ..
-                                    },
-                                ) = &mut head_tag_token
-                                {
-                                    match head_tag {
-                                        Tag::If(..) => {
-                                            sub_ast.finish_build();
-                                            ctx.push_token(template_bytes, head_tag_token);
-                                        }
-                                        _ => panic!("Tag must be balanced"),
-                                    }
-                                } else {
-                                    panic!("Missing head tag");
-                                }
-                            }
-                        }
-                    }
-                    ctx.last_start_pos = i + 2;
+        };
+        let tag_text = bytes_to_str(template_bytes, start_idx, end_idx).trim();
+        // The end tag and the arms of the illegal head tag are dropped with it
+        if let Some((depth, head_name)) = self.illegal_heads.last() {
+            if *depth == self.tag_token_stack.len() {
+                let name = tag_text.split_whitespace().next().unwrap_or_default();
+                if name.strip_prefix("end") == Some(head_name.as_str()) {
+                    self.illegal_heads.pop();
+                    return;
                 }
-                i += 2;
-            }
-            (b'{', b'$') => {
-                if ctx.last_start_pos < i {
-                    let last_pos = ctx.last_start_pos;
-                    let token = Token::new_text(&mut ctx, last_pos, i);
-                    ctx.push_token(template_bytes, token);
-                    ctx.last_start_pos = i;
+                if head_name == "match" && matches!(tag, Tag::Case(_) | Tag::Default) {
+                    return;
                 }
-                ctx.head_symbol_stack.push((Symbol::Env, i + 2));
-                ctx.last_start_pos += 2;
-                i += 2;
             }
-            (b'$', b'}') => {
-                if let Some((Symbol::Env, _)) = ctx.head_symbol_stack.last() {
-                    let (_, start_idx) = ctx.head_symbol_stack.pop().unwrap();
-                    if !bytes[start_idx..i].contains(&b'=') {
-                        panic!("Env symbol missing '=', it should be define like '{{$ key = value $}}'")
-                    }
-                    let env = EnvDefine::new(start_idx, i);
-                    ctx.push_env(env);
-                    ctx.last_start_pos = i + 2;
-                }
-                i += 2;
+        }
+        match tag {
+            Tag::For(..)
+            | Tag::If(..)
+            | Tag::Block(_)
+            | Tag::Macro(..)
+            | Tag::Set(_)
+            | Tag::Match(..)
+            | Tag::With(_)
+            | Tag::File(..) => {
+                let token = Token::new_tag(self, tag, start_idx, end_idx);
+                self.tag_token_stack.push(token);
             }
-            (b'{', b'{') => {
-                if ctx.last_start_pos < i {
-                    let last_pos = ctx.last_start_pos;
-                    let token = Token::new_text(&mut ctx, last_pos, i);
-                    ctx.push_token(template_bytes, token);
-                    ctx.last_start_pos = i;
+            Tag::Case(_) | Tag::Default => {
+                // The previous arm is ended by the next arm
+                if matches!(self.head_tag(), Some(Tag::Case(_) | Tag::Default)) {
+                    self.pop_tag(template_bytes, tag_text, |_| true);
                 }
-                ctx.head_symbol_stack.push((Symbol::Placeholder, i + 2));
-                ctx.last_start_pos += 2;
-                i += 2;
+                if !matches!(self.head_tag(), Some(Tag::Match(..))) {
+                    self.fail(
+                        self.symbol_span(),
+                        String::from("Tag case and default must be in match"),
+                        Some(String::from("Put it between `match` and `endmatch`")),
+                    );
+                    return;
+                }
+                let token = Token::new_tag(self, tag, start_idx, end_idx);
+                self.tag_token_stack.push(token);
             }
-            (b'}', b'}') => {
-                if let Some((Symbol::Placeholder, _)) = ctx.head_symbol_stack.last() {
-                    let (_, start_idx) = ctx.head_symbol_stack.pop().unwrap();
-                    let token = Token::new_placeholder(&mut ctx, start_idx, i);
-                    ctx.push_token(template_bytes, token);
-                    ctx.last_start_pos = i + 2;
+            Tag::EndMatch => {
+                if matches!(self.head_tag(), Some(Tag::Case(_) | Tag::Default)) {
+                    self.pop_tag(template_bytes, tag_text, |_| true);
                 }
-                i += 2;
+                self.pop_tag(template_bytes, tag_text, |tag| {
+                    matches!(tag, Tag::Match(..))
+                });
             }
-            (b'{', b'#') => {
-                if ctx.last_start_pos < i {
-                    let last_pos = ctx.last_start_pos;
-                    let token = Token::new_text(&mut ctx, last_pos, i);
-                    ctx.push_token(template_bytes, token);
-                    ctx.last_start_pos = i;
+            Tag::EndFor => {
+                self.pop_tag(template_bytes, tag_text, |tag| matches!(tag, Tag::For(..)))
+            }
+            Tag::EndIf => self.pop_tag(template_bytes, tag_text, |tag| matches!(tag, Tag::If(..))),
+            Tag::EndBlock => {
+                self.pop_tag(template_bytes, tag_text, |tag| matches!(tag, Tag::Block(_)))
+            }
+            Tag::EndMacro => self.pop_tag(template_bytes, tag_text, |tag| {
+                matches!(tag, Tag::Macro(..))
+            }),
+            Tag::EndSet => self.pop_tag(template_bytes, tag_text, |tag| matches!(tag, Tag::Set(_))),
+            Tag::EndWith => {
+                self.pop_tag(template_bytes, tag_text, |tag| matches!(tag, Tag::With(_)))
+            }
+            Tag::EndFile => {
+                self.pop_tag(template_bytes, tag_text, |tag| matches!(tag, Tag::File(..)))
+            }
+            Tag::Custom(ref custom_tag, _) if custom_tag.has_end_tag() => {
+                let token = Token::new_tag(self, tag, start_idx, end_idx);
+                self.tag_token_stack.push(token);
+            }
+            Tag::EndCustom(ref name) => self.pop_tag(
+                template_bytes,
+                tag_text,
+                |tag| matches!(tag, Tag::Custom(custom_tag, _) if custom_tag.name() == name),
+            ),
+            Tag::Include(..)
+            | Tag::Extends(..)
+            | Tag::Import(..)
+            | Tag::Custom(..)
+            | Tag::Assert(..)
+            | Tag::Error(..) => {
+                // Inline tag, no end tag
+                let token = Token::new_tag(self, tag, start_idx, end_idx);
+                self.push_token(template_bytes, token);
+            }
+        }
+    }
+
+    fn head_tag(&self) -> Option<&Tag> {
+        match self.tag_token_stack.last() {
+            Some(Token::Tag(_, TagExtend { tag, .. })) => Some(tag),
+            _ => None,
+        }
+    }
+
+    /// Pop the head tag when its end tag comes, and push it as a token
+    fn pop_tag(
+        &mut self,
+        template_bytes: &[u8],
+        end_tag_text: &str,
+        is_head_tag: impl Fn(&Tag) -> bool,
+    ) {
+        if let Some(mut head_tag_token) = self.tag_token_stack.pop() {
+            if let Token::Tag(
+                token_ctx,
+                TagExtend {
+                    tag: head_tag,
+                    sub_ast,
+                    end_tag_span,
+                },
+            ) = &mut head_tag_token
+            {
+                if !is_head_tag(head_tag) {
+                    let head_tag_name = tag_name(template_bytes, token_ctx);
+                    // The arm of match is closed by 'endmatch'
+                    let expected = match head_tag {
+                        Tag::Case(_) | Tag::Default => String::from("endmatch"),
+                        _ => format!("end{}", head_tag_name),
+                    };
+                    let message = format!(
+                        "Tag must be balanced, {} does not close {}",
+                        end_tag_text, head_tag_name
+                    );
+                    // The end tag is dropped in lint mode
+                    self.tag_token_stack.push(head_tag_token);
+                    self.fail(
+                        self.symbol_span(),
+                        message,
+                        Some(format!("Did you mean `{}`?", expected)),
+                    );
+                    return;
                 }
-                ctx.now_in_raw = true;
-                ctx.head_symbol_stack.push((Symbol::Raw, i + 2));
-                ctx.last_start_pos += 2;
-                i += 2;
+                sub_ast.finish_build();
+                if !matches!(head_tag, Tag::Case(_) | Tag::Default) {
+                    *end_tag_span = Some((self.last_symbol_head_pos, self.last_symbol_tail_end));
+                }
+                self.push_token(template_bytes, head_tag_token);
+            } else {
+                panic!("Missing head tag");
             }
-            (b'\r', b'\n') => {
-                let last_start_pos = ctx.last_start_pos;
-                if last_start_pos < i {
-                    let token = Token::new_text(&mut ctx, last_start_pos, i);
-                    ctx.push_token(template_bytes, token);
+        } else {
+            let head_tag_name = end_tag_text.strip_prefix("end").unwrap_or(end_tag_text);
+            // The end tag is dropped in lint mode
+            self.fail(
+                self.symbol_span(),
+                format!("Missing head tag of {}", end_tag_text),
+                Some(format!("Remove it, or add `{}` before it", head_tag_name)),
+            );
+        }
+    }
+
+    /// Push the text at the end of line, but whitespaces after Token::Tag will be dropped
+    pub fn push_line_end_text(&mut self, template_bytes: &[u8], start: usize, end: usize) {
+        if start >= end {
+            return;
+        }
+        if self.current_ast().is_tag_last_now()
+            && template_bytes[start..end]
+                .iter()
+                .all(|b| b.is_ascii_whitespace())
+        {
+            return;
+        }
+        let token = Token::new_text(self, start, end);
+        self.push_token(template_bytes, token);
+    }
+}
+
+/// @return End index of the text without the whitespaces at the end
+fn trim_end_whitespace(bytes: &[u8], start: usize, end: usize) -> usize {
+    bytes[start..end]
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())
+        .map_or(start, |pos| start + pos + 1)
+}
+
+/// First word of the tag, like 'for' of '{% for item in items %}'
+fn tag_name<'a>(template_bytes: &'a [u8], token_ctx: &TokenContext) -> &'a str {
+    bytes_to_str(template_bytes, token_ctx.start, token_ctx.end)
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+}
+
+/// Usage of the tags, (name, usage)
+const TAG_USAGES: [(&str, &str); 14] = [
+    (
+        "for",
+        "for item in array [recursive] | for key, value in object",
+    ),
+    ("if", "if a | if a == b | if a != b | if a is [not] defined"),
+    ("include", "include \"name\" [with context]"),
+    ("extends", "extends \"name\""),
+    ("block", "block name"),
+    ("macro", "macro name(param, param=default)"),
+    ("import", "import \"name\" [as alias]"),
+    ("set", "set name"),
+    ("match", "match expression"),
+    ("case", "case value, value"),
+    (
+        "with",
+        "with variable | with name = expression, name = expression",
+    ),
+    ("file", "file \"path\""),
+    ("assert", "assert condition[, \"message\"]"),
+    ("error", "error \"message\""),
+];
+
+/// Names of the end tags and the tags without arguments
+const TAG_KEYWORDS: [&str; 9] = [
+    "endfor", "endif", "endblock", "endmacro", "endset", "default", "endmatch", "endwith",
+    "endfile",
+];
+
+/// Usage of the illegal tag, or the similar tag name of the unsupported tag
+fn tag_hint(tag_text: &str, custom_tags: &[Rc<dyn CustomTag>]) -> Option<String> {
+    let name = tag_text.split_whitespace().next().unwrap_or_default();
+    if let Some((_, usage)) = TAG_USAGES.iter().find(|(tag, _)| *tag == name) {
+        return Some(format!("Usage: `{}`", usage));
+    }
+    let custom_names: Vec<String> = custom_tags
+        .iter()
+        .flat_map(|custom_tag| {
+            let name = custom_tag.name();
+            match custom_tag.has_end_tag() {
+                true => vec![name.to_owned(), format!("end{}", name)],
+                false => vec![name.to_owned()],
+            }
+        })
+        .collect();
+    let candidates = TAG_USAGES
+        .iter()
+        .map(|(tag, _)| *tag)
+        .chain(TAG_KEYWORDS)
+        .chain(custom_names.iter().map(String::as_str));
+    closest_name(name, candidates).map(|similar| format!("Did you mean `{}`?", similar))
+}
+
+/// The most similar name within 2 edits, for the hint 'Did you mean'
+pub(crate) fn closest_name<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Edit distance in chars, the transposition of two adjacent chars is one edit
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+fn generate_tokens(template_bytes: &[u8], options: &FillOptions) -> TemplateASTable {
+    generate_tokens_0(template_bytes, options, false).0
+}
+
+/// Parse the template and collect the problems instead of panic,
+/// the illegal symbols are dropped and the unclosed tags are closed at the end
+pub(crate) fn lint_template<T: AsRef<str>>(
+    template_content: T,
+    options: &FillOptions,
+) -> (ast::Ast, Vec<LintProblem>) {
+    let template_bytes = template_content.as_ref().as_bytes();
+    let (template_ast, problems) = generate_tokens_0(template_bytes, options, true);
+    (to_public_ast(template_bytes, &template_ast), problems)
+}
+
+/// @return (template AST, problems found in lint mode)
+fn generate_tokens_0(
+    template_bytes: &[u8],
+    options: &FillOptions,
+    lint: bool,
+) -> (TemplateASTable, Vec<LintProblem>) {
+    let mut ctx = GenerateTokensContext::new(options.tab_width, &options.custom_tags, lint);
+
+    let bytes = template_bytes;
+    // The syntax selected at the first line is preferred
+    let (syntax, mut i) = match read_syntax_directive(bytes) {
+        Some((Ok(syntax), body_start)) => (syntax, body_start),
+        Some((Err(e), body_start)) => {
+            ctx.fail(
+                (0, body_start),
+                format!("Illegal syntax directive: {}", e),
+                None,
+            );
+            (options.syntax.clone(), body_start)
+        }
+        None => (options.syntax.clone(), 0),
+    };
+    ctx.last_start_pos = i;
+    let symbols = get_symbol_delimiters(&syntax);
+    while i < bytes.len() {
+        // In symbol, find the symbol tail
+        if let Some(&(symbol, start_idx)) = ctx.head_symbol_stack.last() {
+            let tail = get_symbol_tail(&symbols, symbol);
+            if bytes[i..].starts_with(tail) {
+                ctx.head_symbol_stack.pop();
+                let strip_after = is_strip_tail(bytes, start_idx, i);
+                let end_idx = if strip_after { i - 1 } else { i };
+                ctx.last_symbol_tail_end = i + tail.len();
+                let mut trim_line_feed = false;
+                match symbol {
+                    Symbol::Logical => {
+                        // The line of the tag which is alone in it has been dropped,
+                        // so its line feed is not trimmed again
+                        let is_alone = ctx.current_ast().is_empty_line_now();
+                        ctx.push_tag(template_bytes, start_idx, end_idx);
+                        trim_line_feed = options.trim_blocks && !is_alone;
+                    }
+                    Symbol::Env => {
+                        if bytes[start_idx..end_idx].contains(&b'=') {
+                            let env = EnvDefine::new(start_idx, end_idx, ctx.symbol_span());
+                            ctx.push_env(env);
+                        } else {
+                            ctx.fail(ctx.symbol_span(), String::from("Env symbol missing '=', it should be define like '{$ key = value $}'"), None);
+                        }
+                    }
+                    Symbol::Placeholder => {
+                        let token = Token::new_placeholder(&mut ctx, start_idx, end_idx);
+                        ctx.push_token(template_bytes, token);
+                    }
+                    Symbol::Raw => {
+                        let token = Token::new_text(&mut ctx, start_idx, end_idx);
+                        ctx.push_token(template_bytes, token);
+                    }
+                    Symbol::Comment => ctx.push_comment(),
                 }
+                i = skip_after_symbol_tail(bytes, i + tail.len(), strip_after, trim_line_feed);
+                ctx.last_start_pos = i;
+            } else if bytes[i] == b'"' && matches!(symbol, Symbol::Logical | Symbol::Placeholder) {
+                // The symbol tail in string is not the real tail, for example '{% if a == "%}" %}'
+                i = skip_quoted(bytes, i);
+            } else {
+                i += 1;
+            }
+            continue;
+        }
+
+        // Line feed
+        if bytes[i] == b'\n' || bytes[i..].starts_with(b"\r\n") {
+            // Whitespace control: the symbol head with '-' (for example '{%-') will strip
+            // the whitespaces and line feeds before it, so the line is not break here
+            if let Some(strip_head_idx) = find_strip_head(&symbols, bytes, i) {
+                i = strip_head_idx;
+                continue;
+            }
+            let last_start_pos = ctx.last_start_pos;
+            ctx.push_line_end_text(template_bytes, last_start_pos, i);
+            if bytes[i] == b'\r' {
                 ctx.new_line(Some(LineFeed::CRLF));
-                ctx.last_start_pos = i + 2;
                 i += 2;
-            }
-            (b'\n', _) => {
-                let last_start_pos = ctx.last_start_pos;
-                if last_start_pos < i {
-                    let token = Token::new_text(&mut ctx, last_start_pos, i);
-                    ctx.push_token(template_bytes, token);
-                }
+            } else {
                 ctx.new_line(Some(LineFeed::LF));
-                ctx.last_start_pos = i + 1;
                 i += 1;
             }
-            (_, b'\n') => {
+            ctx.last_start_pos = i;
+            continue;
+        }
+
+        // Escaped symbol head, for example '\{{' will be filled as '{{',
+        // and '\\{{' will be filled as '\' followed by the symbol
+        if options.escape_symbols && bytes[i] == b'\\' {
+            let escaped_backslash = bytes.get(i + 1) == Some(&b'\\');
+            let head_start = if escaped_backslash { i + 2 } else { i + 1 };
+            if let Some((_, head, _)) = match_symbol_head(&symbols, bytes, head_start) {
                 let last_start_pos = ctx.last_start_pos;
-                if last_start_pos < i + 1 {
-                    let token = Token::new_text(&mut ctx, last_start_pos, i + 1);
+                // The text includes the first backslash if it is escaped
+                let text_end = if escaped_backslash { i + 1 } else { i };
+                if last_start_pos < text_end {
+                    let token = Token::new_text(&mut ctx, last_start_pos, text_end);
                     ctx.push_token(template_bytes, token);
                 }
-                ctx.new_line(Some(LineFeed::LF));
-                ctx.last_start_pos = i + 2;
-                i += 2;
+                if escaped_backslash {
+                    ctx.last_start_pos = head_start;
+                    i = head_start;
+                } else {
+                    ctx.last_start_pos = i + 1;
+                    i += 1 + head.len();
+                }
+                continue;
             }
-            _ => i += 1,
         }
+
+        // Symbol head
+        if let Some((symbol, head, _)) = match_symbol_head(&symbols, bytes, i) {
+            let lstrip = options.lstrip_blocks && symbol == Symbol::Logical;
+            i = ctx.open_symbol(template_bytes, symbol, i, head.len(), lstrip);
+            continue;
+        }
+
+        i += 1;
+    }
+    // Unterminated symbol is filled as text
+    if let Some(&(symbol, head_end)) = ctx.head_symbol_stack.last() {
+        ctx.last_start_pos = ctx.last_symbol_head_pos;
+        let head = bytes_to_str(bytes, ctx.last_symbol_head_pos, head_end);
+        let tail = str::from_utf8(get_symbol_tail(&symbols, symbol)).unwrap_or_default();
+        ctx.lint(
+            (ctx.last_symbol_head_pos, head_end),
+            format!("Unterminated symbol {}, missing {}", head, tail),
+            Some(format!(
+                "Add `{}` to close it, or escape it as `\\{}` with the option `escape_symbols` if it is text",
+                tail, head
+            )),
+        );
     }
     let last_start_pos = ctx.last_start_pos;
-    if last_start_pos == i_max {
-        if bytes[last_start_pos] == b'\n' {
-            ctx.new_line(Some(LineFeed::LF));
-        } else {
-            let token = Token::new_text(&mut ctx, last_start_pos, bytes.len());
-            ctx.push_token(template_bytes, token);
-        }
-    } else {
-        if ctx.last_start_pos < bytes.len() {
-            let token = Token::new_text(&mut ctx, last_start_pos, bytes.len());
+    ctx.push_line_end_text(template_bytes, last_start_pos, bytes.len());
+    // The arm of match is closed by its match
+    let unclosed: Vec<LintProblem> = ctx
+        .tag_token_stack
+        .iter()
+        .filter_map(|token| match token {
+            Token::Tag(
+                _,
+                TagExtend {
+                    tag: Tag::Case(_) | Tag::Default,
+                    ..
+                },
+            ) => None,
+            Token::Tag(token_ctx, _) => {
+                let name = tag_name(bytes, token_ctx);
+                Some(LintProblem {
+                    message: format!("Unclosed tag {}, missing end{}", name, name),
+                    span: (token_ctx.head_start, token_ctx.tail_end),
+                    severity: Severity::Error,
+                    hint: Some(format!("Add `end{}` after its body", name)),
+                })
+            }
+            _ => None,
+        })
+        .collect();
+    for problem in unclosed {
+        ctx.fail(problem.span, problem.message, problem.hint);
+    }
+    if ctx.problems.is_some() {
+        // Unclosed tags are closed at the end in lint mode, so their bodies can be checked
+        while let Some(mut token) = ctx.tag_token_stack.pop() {
+            if let Token::Tag(_, TagExtend { sub_ast, .. }) = &mut token {
+                sub_ast.finish_build();
+            }
             ctx.push_token(template_bytes, token);
         }
     }
     ctx.template_ast.finish_build();
-    ctx.template_ast
+    (ctx.template_ast, ctx.problems.unwrap_or_default())
 }
 
-#[derive(Debug)]
+/// Read the syntax directive at the first line, like '#!syntax bracket'
+/// @return (syntax or the error message, index of the line after directive)
+fn read_syntax_directive(bytes: &[u8]) -> Option<(Result<Syntax, String>, usize)> {
+    let directive = SYNTAX_DIRECTIVE.as_bytes();
+    if !bytes.starts_with(directive) {
+        return None;
+    }
+    let line_end = bytes
+        .iter()
+        .position(|b| *b == b'\n')
+        .map_or(bytes.len(), |pos| pos + 1);
+    let spec = bytes_to_str(bytes, directive.len(), line_end);
+    Some((spec.parse(), line_end))
+}
+
+/// @return Vec<(symbol, head, tail)>, and the longer head is in front
+fn get_symbol_delimiters(syntax: &Syntax) -> Vec<(Symbol, &[u8], &[u8])> {
+    let mut symbols: Vec<(Symbol, &[u8], &[u8])> = vec![
+        (Symbol::Logical, &syntax.logical.0, &syntax.logical.1),
+        (Symbol::Env, &syntax.env.0, &syntax.env.1),
+        (
+            Symbol::Placeholder,
+            &syntax.placeholder.0,
+            &syntax.placeholder.1,
+        ),
+        (Symbol::Raw, &syntax.raw.0, &syntax.raw.1),
+        (Symbol::Comment, &syntax.comment.0, &syntax.comment.1),
+    ]
+    .into_iter()
+    .map(|(symbol, head, tail)| (symbol, head.as_bytes(), tail.as_bytes()))
+    .collect();
+    symbols.sort_by_key(|(_, head, _)| std::cmp::Reverse(head.len()));
+    symbols
+}
+
+fn get_symbol_tail<'a>(symbols: &[(Symbol, &[u8], &'a [u8])], symbol: Symbol) -> &'a [u8] {
+    symbols.iter().find(|(s, ..)| *s == symbol).unwrap().2
+}
+
+fn match_symbol_head<'a>(
+    symbols: &[(Symbol, &'a [u8], &'a [u8])],
+    bytes: &[u8],
+    start: usize,
+) -> Option<(Symbol, &'a [u8], &'a [u8])> {
+    let rest = bytes.get(start..)?;
+    symbols
+        .iter()
+        .find(|(_, head, _)| rest.starts_with(head))
+        .copied()
+}
+
+/// @return Index after the closing quote, or after the opening quote if it is not closed
+fn skip_quoted(bytes: &[u8], quote_start: usize) -> usize {
+    let mut i = quote_start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    quote_start + 1
+}
+
+/// Whitespace control, the symbol tail with '-' (for example '-%}') will strip the whitespaces after it
+fn is_strip_tail(bytes: &[u8], content_start: usize, tail_start: usize) -> bool {
+    tail_start > content_start && bytes[tail_start - 1] == b'-'
+}
+
+/// Whitespace control, find the symbol head with '-' (for example '{%-') after the whitespaces
+/// @return Index of the symbol head
+fn find_strip_head(
+    symbols: &[(Symbol, &[u8], &[u8])],
+    bytes: &[u8],
+    start: usize,
+) -> Option<usize> {
+    let mut i = start;
+    while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+        i += 1;
+    }
+    let (_, head, _) = match_symbol_head(symbols, bytes, i)?;
+    if bytes.get(i + head.len()) == Some(&b'-') {
+        Some(i)
+    } else {
+        None
+    }
+}
+
+/// @return Index of the content after the symbol tail
+fn skip_after_symbol_tail(
+    bytes: &[u8],
+    tail_end: usize,
+    strip_whitespace: bool,
+    trim_line_feed: bool,
+) -> usize {
+    let rest = &bytes[tail_end..];
+    if strip_whitespace {
+        tail_end
+            + rest
+                .iter()
+                .position(|b| !b.is_ascii_whitespace())
+                .unwrap_or(rest.len())
+    } else if trim_line_feed && rest.starts_with(b"\r\n") {
+        tail_end + 2
+    } else if trim_line_feed && rest.starts_with(b"\n") {
+        tail_end + 1
+    } else {
+        tail_end
+    }
+}
+
+#[derive(Debug, Clone)]
 enum Tag {
-    /// for [item] in [array]
-    For(String, String),
+    /// for [item] in [array] recursive, or for [key], [value] in [object]
+    For(String, Option<String>, String, bool),
     EndFor,
-    /// if [left_type] [left] [operator] [right_type] [right]
-    If(ExpressionType, String, String, ExpressionType, String),
+    /// if [condition]
+    If(Condition),
     EndIf,
+    /// include [name_type] [name] with [context]
+    Include(ExpressionType, String, Option<String>),
+    /// extends [name_type] [name]
+    Extends(ExpressionType, String),
+    /// block [name]
+    Block(String),
+    EndBlock,
+    /// macro [name]([params])
+    Macro(String, Vec<MacroParam>),
+    EndMacro,
+    /// import [name_type] [name] as [alias]
+    Import(ExpressionType, String, Option<String>),
+    /// set [name], capture the filled content into variable
+    Set(String),
+    EndSet,
+    /// match [subject_type] [subject]
+    Match(ExpressionType, String),
+    /// case [value_type] [value], [value_type] [value], it is ended by next case, default or endmatch
+    Case(Vec<(ExpressionType, String)>),
+    Default,
+    EndMatch,
+    With(WithScope),
+    EndWith,
+    /// file [path_type] [path], the path can contain placeholders, like "{{ name }}.sql"
+    File(ExpressionType, String),
+    EndFile,
+    /// [name] [body], the tag registered by `FillOptions::custom_tags` with the arguments parsed from body
+    Custom(Rc<dyn CustomTag>, Value),
+    /// end[name]
+    EndCustom(String),
+    /// assert [condition], [message_type] [message], the message can contain placeholders
+    Assert(Condition, ExpressionType, String),
+    /// error [message_type] [message], the message can contain placeholders
+    Error(ExpressionType, String),
+}
+
+/// [left_type] [left] [operator] [right_type] [right],
+/// the right is not used if the operator is 'is defined' or 'is not defined'
+#[derive(Debug, Clone)]
+struct Condition {
+    left_type: ExpressionType,
+    left: String,
+    operator: String,
+    right_type: ExpressionType,
+    right: String,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone)]
+enum WithScope {
+    /// with [name] = [expression], [name] = [expression]
+    Bindings(Vec<(String, String)>),
+    /// with [variable], only the variable is visible as data
+    Data(String),
+}
+
+/// Macro parameter, like 'name' or 'nullable=true'
+#[derive(Debug, Clone)]
+struct MacroParam {
+    name: String,
+    /// (default_type, default)
+    default: Option<(ExpressionType, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 enum ExpressionType {
     VariableName,
     String,
@@ -633,100 +1463,651 @@ enum ExpressionType {
     Boolean,
 }
 
-fn generate_tag(tag_bytes: &[u8]) -> Tag {
+/// Error message if the tag is illegal or unsupported
+fn generate_tag(tag_bytes: &[u8], custom_tags: &[Rc<dyn CustomTag>]) -> Result<Tag, String> {
     let tag_text = str::from_utf8(tag_bytes)
         .expect("Convert to str fail")
         .trim();
-    match tag_text {
+    let tag = match tag_text {
         "endfor" => Tag::EndFor,
         "endif" => Tag::EndIf,
+        "endblock" => Tag::EndBlock,
+        "endmacro" => Tag::EndMacro,
+        "endset" => Tag::EndSet,
+        "default" => Tag::Default,
+        "endmatch" => Tag::EndMatch,
+        "endwith" => Tag::EndWith,
+        "endfile" => Tag::EndFile,
+        _ if tag_text.starts_with("endblock ") => Tag::EndBlock,
         _ => {
             let tag_text = normalize_spaces(tag_text);
-            if tag_text.starts_with("for ") {
-                let tag_slices: Vec<&str> = tag_text.splitn(4, ' ').collect();
-                if tag_slices.len() != 4 || *tag_slices.get(2).unwrap() != "in" {
-                    panic!("Illegal expression: for")
+            if let Some(for_text) = tag_text.strip_prefix("for ") {
+                // for item in array | for key, value in object
+                let (names, collect_name) = for_text
+                    .split_once(" in ")
+                    .ok_or_else(|| String::from("Illegal expression: for"))?;
+                let names: Vec<&str> = names.split(',').map(str::trim).collect();
+                let (item_name, value_name) = match names.as_slice() {
+                    [item_name] => (*item_name, None),
+                    [key_name, value_name] => (*key_name, Some(value_name.to_string())),
+                    _ => return Err(String::from("Illegal expression: for")),
+                };
+                // Recursive loop re-enters the loop body by 'loop(children)'
+                let (collect_name, recursive) = match collect_name.strip_suffix(" recursive") {
+                    Some(collect_name) => (collect_name, true),
+                    None => (collect_name, false),
+                };
+                let is_variable = |name: &str| {
+                    try_assess_expression(name) == Some(ExpressionType::VariableName)
+                        && !name.contains(' ')
+                };
+                if !is_variable(item_name)
+                    || !value_name.as_deref().is_none_or(is_variable)
+                    || !is_variable(collect_name)
+                    || recursive && value_name.is_some()
+                {
+                    return Err(String::from("Illegal expression: for"));
                 }
-                let item_name = tag_slices.get(1).unwrap().to_string();
-                let collect_name = tag_slices.get(3).unwrap().to_string();
-                Tag::For(item_name, collect_name)
-            } else if tag_text.starts_with("if ") {
-                let tag_slices: Vec<&str> = tag_text.splitn(4, ' ').collect();
-                if tag_slices.len() == 2 {
-                    let expression = tag_slices.get(1).unwrap().trim();
-                    let expression_type = assess_expression(expression);
-                    if expression_type != ExpressionType::VariableName
-                        && expression_type != ExpressionType::Boolean
-                    {
-                        panic!("Illegal expression: if")
-                    }
-                    Tag::If(
-                        expression_type,
-                        expression.to_owned(),
-                        "==".to_string(),
-                        ExpressionType::Boolean,
-                        "true".to_owned(),
-                    )
-                } else if tag_slices.len() == 4
-                    && (*tag_slices.get(2).unwrap() == "==" || *tag_slices.get(2).unwrap() == "!=")
+                Tag::For(
+                    item_name.to_owned(),
+                    value_name,
+                    collect_name.to_string(),
+                    recursive,
+                )
+            } else if let Some(condition) = tag_text.strip_prefix("if ") {
+                Tag::If(generate_condition(condition, "if")?)
+            } else if let Some(include_text) = tag_text.strip_prefix("include ") {
+                // include "name" | include "name" with context
+                let tag_slices = split_outside_quotes(include_text, ' ');
+                let with = match tag_slices.as_slice() {
+                    [_] => None,
+                    [_, "with", context] => Some(context.to_string()),
+                    _ => return Err(String::from("Illegal expression: include")),
+                };
+                let name = tag_slices[0];
+                let name_type = assess_tag_expression(name)?;
+                if name_type != ExpressionType::VariableName && name_type != ExpressionType::String
                 {
-                    let expression_left = tag_slices.get(1).unwrap().trim();
-                    let expression_left_type = assess_expression(expression_left);
-                    let expression_right = tag_slices.get(3).unwrap().trim();
-                    let expression_right_type = assess_expression(expression_right);
-                    Tag::If(
-                        expression_left_type,
-                        expression_left.to_owned(),
-                        tag_slices.get(2).unwrap().to_string(),
-                        expression_right_type,
-                        expression_right.to_owned(),
-                    )
-                } else {
-                    panic!("Illegal expression: if")
+                    return Err(String::from("Illegal expression: include"));
                 }
+                if let Some(with) = &with {
+                    if assess_tag_expression(with)? != ExpressionType::VariableName {
+                        return Err(String::from("Illegal expression: include"));
+                    }
+                }
+                Tag::Include(name_type, name.to_owned(), with)
+            } else if let Some(name) = tag_text.strip_prefix("extends ") {
+                let name_type = assess_tag_expression(name)?;
+                if name_type != ExpressionType::VariableName && name_type != ExpressionType::String
+                {
+                    return Err(String::from("Illegal expression: extends"));
+                }
+                Tag::Extends(name_type, name.to_owned())
+            } else if let Some(signature) = tag_text.strip_prefix("macro ") {
+                // macro name(param_1, param_2=default)
+                let (name, params) = signature
+                    .strip_suffix(')')
+                    .and_then(|signature| signature.split_once('('))
+                    .ok_or_else(|| String::from("Illegal expression: macro"))?;
+                let name = name.trim();
+                if assess_tag_expression(name)? != ExpressionType::VariableName {
+                    return Err(String::from("Illegal expression: macro"));
+                }
+                let params = split_outside_quotes(params, ',')
+                    .into_iter()
+                    .map(str::trim)
+                    .filter(|param| !param.is_empty())
+                    .map(|param| {
+                        let (name, default) = match param.split_once('=') {
+                            Some((name, default)) => {
+                                let default = default.trim();
+                                (
+                                    name.trim(),
+                                    Some((assess_tag_expression(default)?, default.to_owned())),
+                                )
+                            }
+                            None => (param, None),
+                        };
+                        if assess_tag_expression(name)? != ExpressionType::VariableName {
+                            return Err(format!("Illegal macro parameter: {}", param));
+                        }
+                        Ok(MacroParam {
+                            name: name.to_owned(),
+                            default,
+                        })
+                    })
+                    .collect::<Result<_, String>>()?;
+                Tag::Macro(name.to_owned(), params)
+            } else if let Some(import_text) = tag_text.strip_prefix("import ") {
+                // import "name" | import "name" as alias
+                let tag_slices = split_outside_quotes(import_text, ' ');
+                let alias = match tag_slices.as_slice() {
+                    [_] => None,
+                    [_, "as", alias]
+                        if assess_tag_expression(alias)? == ExpressionType::VariableName =>
+                    {
+                        Some(alias.to_string())
+                    }
+                    _ => return Err(String::from("Illegal expression: import")),
+                };
+                let name = tag_slices[0];
+                let name_type = assess_tag_expression(name)?;
+                if name_type != ExpressionType::VariableName && name_type != ExpressionType::String
+                {
+                    return Err(String::from("Illegal expression: import"));
+                }
+                Tag::Import(name_type, name.to_owned(), alias)
+            } else if let Some(assert_text) = tag_text.strip_prefix("assert ") {
+                // assert condition | assert condition, "message"
+                let (condition, (message_type, message)) =
+                    match split_outside_quotes(assert_text, ',').as_slice() {
+                        [condition] => (
+                            condition.trim(),
+                            (
+                                ExpressionType::String,
+                                format!("\"Assertion failed: {}\"", condition.trim()),
+                            ),
+                        ),
+                        [condition, message] => (
+                            condition.trim(),
+                            generate_message(message.trim(), "assert")?,
+                        ),
+                        _ => return Err(String::from("Illegal expression: assert")),
+                    };
+                Tag::Assert(
+                    generate_condition(condition, "assert")?,
+                    message_type,
+                    message,
+                )
+            } else if let Some(message) = tag_text.strip_prefix("error ") {
+                let (message_type, message) = generate_message(message, "error")?;
+                Tag::Error(message_type, message)
+            } else if let Some(path) = tag_text.strip_prefix("file ") {
+                let path_type = assess_tag_expression(path)?;
+                if path_type != ExpressionType::VariableName && path_type != ExpressionType::String
+                {
+                    return Err(String::from("Illegal expression: file"));
+                }
+                Tag::File(path_type, path.to_owned())
+            } else if let Some(with_text) = tag_text.strip_prefix("with ") {
+                if !with_text.contains('=') {
+                    if assess_tag_expression(with_text)? != ExpressionType::VariableName
+                        || with_text.contains(' ')
+                    {
+                        return Err(String::from("Illegal expression: with"));
+                    }
+                    Tag::With(WithScope::Data(with_text.to_owned()))
+                } else {
+                    let bindings = split_outside_quotes(with_text, ',')
+                        .into_iter()
+                        .map(|binding| {
+                            let (name, expression) = binding
+                                .split_once('=')
+                                .map(|(name, expression)| (name.trim(), expression.trim()))
+                                .ok_or_else(|| String::from("Illegal expression: with"))?;
+                            if assess_tag_expression(name)? != ExpressionType::VariableName {
+                                return Err(String::from("Illegal expression: with"));
+                            }
+                            assess_tag_expression(expression)?;
+                            Ok((name.to_owned(), expression.to_owned()))
+                        })
+                        .collect::<Result<_, String>>()?;
+                    Tag::With(WithScope::Bindings(bindings))
+                }
+            } else if let Some(subject) = tag_text.strip_prefix("match ") {
+                Tag::Match(assess_tag_expression(subject)?, subject.to_owned())
+            } else if let Some(values) = tag_text.strip_prefix("case ") {
+                let values = split_outside_quotes(values, ',')
+                    .into_iter()
+                    .map(str::trim)
+                    .map(|value| {
+                        if value.is_empty() {
+                            return Err(String::from("Illegal expression: case"));
+                        }
+                        Ok((assess_tag_expression(value)?, value.to_owned()))
+                    })
+                    .collect::<Result<_, String>>()?;
+                Tag::Case(values)
+            } else if let Some(name) = tag_text.strip_prefix("set ") {
+                if assess_tag_expression(name)? != ExpressionType::VariableName
+                    || name.contains(' ')
+                {
+                    return Err(String::from("Illegal expression: set"));
+                }
+                Tag::Set(name.to_owned())
+            } else if let Some(name) = tag_text.strip_prefix("block ") {
+                if assess_tag_expression(name)? != ExpressionType::VariableName
+                    || name.contains(' ')
+                {
+                    return Err(String::from("Illegal expression: block"));
+                }
+                Tag::Block(name.to_owned())
             } else {
-                panic!("Unsupported tag: {}", tag_text)
+                generate_custom_tag(&tag_text, custom_tags)?
+                    .ok_or_else(|| format!("Unsupported tag: {}", tag_text))?
+            }
+        }
+    };
+    Ok(tag)
+}
+
+/// Condition is like 'a', 'a == b', 'a != "b"', 'a is defined' or 'a is not defined'
+fn generate_condition(condition_text: &str, tag_name: &str) -> Result<Condition, String> {
+    let defined_condition = [" is defined", " is not defined"]
+        .into_iter()
+        .find_map(|suffix| Some((condition_text.strip_suffix(suffix)?, &suffix[1..])));
+    if let Some((variable, operator)) = defined_condition {
+        if assess_tag_expression(variable)? != ExpressionType::VariableName
+            || variable.contains(' ')
+        {
+            return Err(format!("Illegal expression: {}", tag_name));
+        }
+        return Ok(Condition {
+            left_type: ExpressionType::VariableName,
+            left: variable.to_owned(),
+            operator: operator.to_owned(),
+            right_type: ExpressionType::Boolean,
+            right: "true".to_owned(),
+        });
+    }
+    let condition_slices: Vec<&str> = condition_text.splitn(3, ' ').collect();
+    if condition_slices.len() == 1 {
+        let expression = condition_slices.first().unwrap().trim();
+        let expression_type = assess_tag_expression(expression)?;
+        if expression_type != ExpressionType::VariableName
+            && expression_type != ExpressionType::Boolean
+        {
+            return Err(format!("Illegal expression: {}", tag_name));
+        }
+        Ok(Condition {
+            left_type: expression_type,
+            left: expression.to_owned(),
+            operator: "==".to_string(),
+            right_type: ExpressionType::Boolean,
+            right: "true".to_owned(),
+        })
+    } else if condition_slices.len() == 3
+        && (*condition_slices.get(1).unwrap() == "==" || *condition_slices.get(1).unwrap() == "!=")
+    {
+        let expression_left = condition_slices.first().unwrap().trim();
+        let expression_right = condition_slices.get(2).unwrap().trim();
+        Ok(Condition {
+            left_type: assess_tag_expression(expression_left)?,
+            left: expression_left.to_owned(),
+            operator: condition_slices.get(1).unwrap().to_string(),
+            right_type: assess_tag_expression(expression_right)?,
+            right: expression_right.to_owned(),
+        })
+    } else {
+        Err(format!("Illegal expression: {}", tag_name))
+    }
+}
+
+/// Message of the tags 'assert' and 'error' is a string or a variable
+fn generate_message(message: &str, tag_name: &str) -> Result<(ExpressionType, String), String> {
+    let message_type = assess_tag_expression(message)?;
+    if message_type != ExpressionType::VariableName && message_type != ExpressionType::String {
+        return Err(format!("Illegal expression: {}", tag_name));
+    }
+    Ok((message_type, message.to_owned()))
+}
+
+/// The tag is custom if its first word is the name of a custom tag, or 'end' + name of a custom block tag
+/// None if the tag is not custom
+fn generate_custom_tag(
+    tag_text: &str,
+    custom_tags: &[Rc<dyn CustomTag>],
+) -> Result<Option<Tag>, String> {
+    let (name, body) = tag_text.split_once(' ').unwrap_or((tag_text, ""));
+    for custom_tag in custom_tags {
+        if custom_tag.name() == name {
+            let args = custom_tag
+                .parse(body)
+                .map_err(|e| format!("Illegal expression: {}, {}", name, e))?;
+            return Ok(Some(Tag::Custom(Rc::clone(custom_tag), args)));
+        }
+        if custom_tag.has_end_tag()
+            && body.is_empty()
+            && name.strip_prefix("end") == Some(custom_tag.name())
+        {
+            return Ok(Some(Tag::EndCustom(custom_tag.name().to_owned())));
+        }
+    }
+    Ok(None)
+}
+
+fn to_public_ast(template_bytes: &[u8], template_ast: &TemplateASTable) -> ast::Ast {
+    let envs = template_ast
+        .custom_envs
+        .iter()
+        .map(|env| {
+            let (key, value) = bytes_to_str(template_bytes, env.start, env.end)
+                .split_once('=')
+                .unwrap();
+            ast::EnvNode {
+                span: Span::new(env.span.0, env.span.1),
+                key: key.trim().to_owned(),
+                value: value.trim().to_owned(),
             }
+        })
+        .collect();
+    let lines = template_ast
+        .syntax_lines
+        .iter()
+        .map(|line| ast::Line {
+            indent: line
+                .indent
+                .iter()
+                .flatten()
+                .map(|(start, end)| Span::new(*start, *end))
+                .collect(),
+            indent_width: line.indent_len,
+            nodes: line
+                .tokens
+                .iter()
+                .map(|token| to_public_node(template_bytes, token))
+                .collect(),
+            line_feed: line.line_feed.as_ref().map(|line_feed| match line_feed {
+                LineFeed::LF => "\n".to_owned(),
+                LineFeed::CRLF => "\r\n".to_owned(),
+            }),
+            env_count: line.env_define_cnt,
+            comment_count: line.comment_cnt,
+        })
+        .collect();
+    ast::Ast {
+        envs,
+        lines,
+        min_indent_width: template_ast.min_indent_len,
+    }
+}
+
+fn to_public_node(template_bytes: &[u8], token: &Token) -> ast::Node {
+    let (token_ctx, kind) = match token {
+        Token::Text(token_ctx) => (
+            token_ctx,
+            ast::NodeKind::Text {
+                text: bytes_to_str(template_bytes, token_ctx.start, token_ctx.end).to_owned(),
+            },
+        ),
+        Token::Placeholder(token_ctx) => (
+            token_ctx,
+            ast::NodeKind::Placeholder {
+                expression: bytes_to_str(template_bytes, token_ctx.start, token_ctx.end)
+                    .trim()
+                    .to_owned(),
+            },
+        ),
+        Token::Tag(token_ctx, tag_ext) => {
+            let is_inline = match &tag_ext.tag {
+                Tag::Include(..)
+                | Tag::Extends(..)
+                | Tag::Import(..)
+                | Tag::Assert(..)
+                | Tag::Error(..) => true,
+                Tag::Custom(custom_tag, _) => !custom_tag.has_end_tag(),
+                _ => false,
+            };
+            (
+                token_ctx,
+                ast::NodeKind::Tag(ast::TagNode {
+                    tag: to_public_tag(&tag_ext.tag),
+                    end_span: tag_ext
+                        .end_tag_span
+                        .map(|(start, end)| Span::new(start, end)),
+                    body: (!is_inline).then(|| to_public_ast(template_bytes, &tag_ext.sub_ast)),
+                }),
+            )
         }
+    };
+    ast::Node {
+        span: Span::new(token_ctx.head_start, token_ctx.tail_end),
+        first_in_line: token_ctx.first_in_line,
+        kind,
     }
 }
 
+fn to_public_tag(tag: &Tag) -> ast::Tag {
+    match tag {
+        Tag::For(item, value, array, recursive) => ast::Tag::For {
+            item: item.to_owned(),
+            value: value.to_owned(),
+            array: array.to_owned(),
+            recursive: *recursive,
+        },
+        Tag::If(condition) => ast::Tag::If {
+            condition: to_public_condition(condition),
+        },
+        Tag::Include(name_type, name, with) => ast::Tag::Include {
+            template: to_public_expression(name_type, name),
+            with: with.to_owned(),
+        },
+        Tag::Extends(name_type, name) => ast::Tag::Extends {
+            template: to_public_expression(name_type, name),
+        },
+        Tag::Block(name) => ast::Tag::Block {
+            name: name.to_owned(),
+        },
+        Tag::Macro(name, params) => ast::Tag::Macro {
+            name: name.to_owned(),
+            params: params
+                .iter()
+                .map(|param| ast::MacroParam {
+                    name: param.name.to_owned(),
+                    default: param
+                        .default
+                        .as_ref()
+                        .map(|(default_type, default)| to_public_expression(default_type, default)),
+                })
+                .collect(),
+        },
+        Tag::Import(name_type, name, alias) => ast::Tag::Import {
+            template: to_public_expression(name_type, name),
+            alias: alias.to_owned(),
+        },
+        Tag::Set(name) => ast::Tag::Set {
+            name: name.to_owned(),
+        },
+        Tag::Match(subject_type, subject) => ast::Tag::Match {
+            subject: to_public_expression(subject_type, subject),
+        },
+        Tag::Case(values) => ast::Tag::Case {
+            values: values
+                .iter()
+                .map(|(value_type, value)| to_public_expression(value_type, value))
+                .collect(),
+        },
+        Tag::Default => ast::Tag::Default,
+        Tag::With(WithScope::Bindings(bindings)) => ast::Tag::With {
+            bindings: bindings.to_owned(),
+        },
+        Tag::With(WithScope::Data(name)) => ast::Tag::WithData {
+            variable: name.to_owned(),
+        },
+        Tag::File(path_type, path) => ast::Tag::File {
+            path: to_public_expression(path_type, path),
+        },
+        Tag::Assert(condition, message_type, message) => ast::Tag::Assert {
+            condition: to_public_condition(condition),
+            message: to_public_expression(message_type, message),
+        },
+        Tag::Error(message_type, message) => ast::Tag::Error {
+            message: to_public_expression(message_type, message),
+        },
+        Tag::Custom(custom_tag, args) => ast::Tag::Custom {
+            tag: custom_tag.name().to_owned(),
+            args: args.to_owned(),
+        },
+        // End tags are not tokens, they are popped with their head tags
+        _ => panic!("An impossible error when parse tag token"),
+    }
+}
+
+fn to_public_condition(condition: &Condition) -> ast::Condition {
+    let has_right = condition.operator == "==" || condition.operator == "!=";
+    ast::Condition {
+        left: to_public_expression(&condition.left_type, &condition.left),
+        operator: condition.operator.to_owned(),
+        right: has_right.then(|| to_public_expression(&condition.right_type, &condition.right)),
+    }
+}
+
+/// None if the text is not a valid expression
+pub(crate) fn parse_expression(expression: &str) -> Option<ast::Expression> {
+    try_assess_expression(expression)
+        .map(|expression_type| to_public_expression(&expression_type, expression))
+}
+
+fn to_public_expression(expression_type: &ExpressionType, expression: &str) -> ast::Expression {
+    ast::Expression {
+        kind: match expression_type {
+            ExpressionType::VariableName => ast::ExpressionKind::Variable,
+            ExpressionType::String => ast::ExpressionKind::String,
+            ExpressionType::Number => ast::ExpressionKind::Number,
+            ExpressionType::Boolean => ast::ExpressionKind::Boolean,
+        },
+        text: expression.to_owned(),
+    }
+}
+
+/// Error message if the expression in tag is not valid
+fn assess_tag_expression(variable_name: &str) -> Result<ExpressionType, String> {
+    try_assess_expression(variable_name)
+        .ok_or_else(|| format!("Unvalid variable name: {}", variable_name))
+}
+
 /// Valid variable name is start with a-z or A-Z or _ or $ (System variable name usually start with $)
 /// Valid string is wrapped in '"' (For example, "abc")
 /// Valid number is only digits (For example, 123 or 123.1)
-fn assess_expression(variable_name: &str) -> ExpressionType {
+fn try_assess_expression(variable_name: &str) -> Option<ExpressionType> {
     if variable_name == "true" || variable_name == "false" {
-        return ExpressionType::Boolean;
+        return Some(ExpressionType::Boolean);
     }
     let mut chars = variable_name.chars();
     if let Some(first) = chars.next() {
         if first.is_alphabetic() || first == '_' || first == '$' {
-            return ExpressionType::VariableName;
+            return Some(ExpressionType::VariableName);
         } else if first == '"' && variable_name.len() >= 2 {
             if let Some(last) = chars.last() {
                 if last == '"' {
-                    return ExpressionType::String;
+                    return Some(ExpressionType::String);
                 }
             }
         } else if first.is_numeric() && variable_name.parse::<f64>().is_ok() {
-            return ExpressionType::Number;
+            return Some(ExpressionType::Number);
         }
     }
-    panic!("Unvalid variable name: {}", variable_name)
+    None
 }
 
 struct AutoDataContext<'a> {
     scope_stack: Rc<RefCell<Vec<Value>>>,
     sys: HashMap<&'a str, String>,
     data: &'a Value,
+    options: &'a FillOptions,
+    /// Names of the templates which are being included, for cycle detection
+    include_stack: Rc<RefCell<Vec<String>>>,
+    /// Block defines by name, the define of child template is in front of its parent's
+    blocks: Rc<HashMap<String, Vec<BlockDefine>>>,
+    /// (name, index of define) of the blocks which are being filled, for 'super()'
+    block_stack: Vec<(String, usize)>,
+    /// Macros by name, the imported macro is named like 'alias.name' if it is imported with alias
+    macros: HashMap<String, Rc<MacroDefine>>,
+    /// (name, content) of the block which content is captured when it is filled first
+    captured_block: Option<(String, Option<Filled>)>,
+    /// (path, content) of the files emitted by the tag 'file'
+    files: Vec<(String, String)>,
+    /// Recursive loops which are being filled, for 'loop(children)'
+    recursive_loops: Vec<RecursiveLoop>,
+    /// Error which aborts the filling, the filling stops when it is set
+    error: Option<FillError>,
+    /// (fill depth, message) of the failure which aborts the filling out of the recovery mode,
+    /// it becomes the error when it is located
+    pending_error: Rc<RefCell<Option<(usize, String)>>>,
+    /// Depth of the values which are being filled by the filter 'render'
+    render_depth: usize,
+    /// Depth of the macros which are being filled
+    macro_depth: usize,
+    /// Count of the placeholders which are filled as not found
+    missing_count: usize,
+    /// Problems which are recovered, None if the filling panics at the problem
+    recovery: Option<Rc<RefCell<Recovery>>>,
+    /// Depth of the templates which are being filled, the problems are located at the token of the depth
+    fill_depth: usize,
+    /// Depth of the texts which are being filled out of the template (for example the message of 'error'),
+    /// their problems are located at the token where the texts are
+    detached_depth: usize,
+    /// Usage of the data which is reported, None if it is not recorded
+    report: Option<Rc<RefCell<ReportState>>>,
+    /// Data paths of the scope variables which are from the data, like `table.columns[]` of the loop item
+    scope_origins: Rc<RefCell<Vec<HashMap<String, String>>>>,
+    /// Path of the data of the context in the data of filling, like `table` of 'with table'
+    data_origin: Option<String>,
+    /// Origins of the filled tokens if the source map is generated
+    source_map: Option<Rc<RefCell<SourceMapState>>>,
+}
+
+/// Problems of the filling in the recovery mode
+#[derive(Debug, Default)]
+struct Recovery {
+    /// (fill depth, severity, message) of the problems which are not located yet
+    pending: Vec<(usize, Severity, String)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Recursive Tag::For and the template source where it is defined
+#[derive(Debug, Clone)]
+struct RecursiveLoop {
+    source: Rc<str>,
+    item_name: String,
+    sub_ast: Rc<TemplateASTable>,
+    /// Start at 0
+    depth: usize,
+    /// Index of the loop stat if the usage is reported
+    stat_index: Option<usize>,
+}
+
+/// Macro and the template source where it is defined
+#[derive(Debug)]
+struct MacroDefine {
+    source: Rc<str>,
+    params: Vec<MacroParam>,
+    sub_ast: TemplateASTable,
+}
+
+/// Block and the template source where it is defined
+#[derive(Debug)]
+struct BlockDefine {
+    source: Rc<str>,
+    sub_ast: TemplateASTable,
 }
 
 impl<'a> AutoDataContext<'a> {
-    pub fn new(data: &'a Value) -> Self {
+    pub fn new(data: &'a Value, options: &'a FillOptions) -> Self {
         let mut s = Self {
             sys: HashMap::new(),
             scope_stack: Rc::new(RefCell::new(Vec::new())),
             data,
+            options,
+            include_stack: Rc::new(RefCell::new(Vec::new())),
+            blocks: Rc::new(HashMap::new()),
+            block_stack: Vec::new(),
+            macros: HashMap::new(),
+            captured_block: None,
+            files: Vec::new(),
+            recursive_loops: Vec::new(),
+            error: None,
+            pending_error: Rc::new(RefCell::new(None)),
+            render_depth: 0,
+            macro_depth: 0,
+            missing_count: 0,
+            recovery: None,
+            fill_depth: 0,
+            detached_depth: 0,
+            report: None,
+            scope_origins: Rc::new(RefCell::new(Vec::new())),
+            data_origin: None,
+            source_map: None,
         };
         // setting system env value
         s.set_sys("$now", Local::now().format("%Y-%m-%d %H:%M:%S").to_string());
@@ -736,6 +2117,7 @@ impl<'a> AutoDataContext<'a> {
     }
 
     pub fn get_string(&self, key: &str) -> Option<String> {
+        self.mark_used(key);
         // 1st, scope (step-by-step loop)
         for scope in Rc::clone(&self.scope_stack).borrow().iter().rev() {
             if let Some(v) = self.get_string_by_step_in_key(scope, key) {
@@ -776,16 +2158,27 @@ impl<'a> AutoDataContext<'a> {
         None
     }
 
+    pub fn get_value(&self, key: &str) -> Option<Value> {
+        // 1st, scope (step-by-step loop)
+        for scope in Rc::clone(&self.scope_stack).borrow().iter().rev() {
+            if let Some(val) = self.get_by_step_in_key(scope, key) {
+                return Some(val.clone());
+            }
+        }
+        // 2nd, custom global data(step-by-step loop)
+        self.get_by_step_in_key(self.data, key).cloned()
+    }
+
     fn get_string_by_step_in_key(&self, data: &Value, key: &str) -> Option<String> {
         if let Some(val) = self.get_by_step_in_key(data, key) {
-            return Some(self.to_pure_string(&val));
+            return Some(self.to_pure_string(val));
         }
         None
     }
 
     fn get_by_step_in_key<'b>(&self, data: &'b Value, key: &str) -> Option<&'b Value> {
         if !key.contains(".") {
-            return data.get(key).map_or(None, |v| Some(v));
+            return data.get(key);
         }
 
         let mut target = data;
@@ -794,11 +2187,7 @@ impl<'a> AutoDataContext<'a> {
             if !target.is_object() {
                 return None;
             }
-            let v = target.get(k);
-            if v.is_none() {
-                return None;
-            }
-            target = v.unwrap();
+            target = target.get(k)?;
         }
         Some(target)
     }
@@ -811,7 +2200,8 @@ impl<'a> AutoDataContext<'a> {
         }
     }
 
-    pub fn set_scope_with_string(&self, key: &'a str, val: String) {
+    pub fn set_scope_with_string(&self, key: &str, val: String) {
+        self.set_scope_origin(key, None);
         if let Some(scope) = Rc::clone(&self.scope_stack).borrow_mut().last_mut() {
             scope[key] = Value::String(val);
             return;
@@ -819,7 +2209,8 @@ impl<'a> AutoDataContext<'a> {
         panic!("No data scope be found, need to add scope first")
     }
 
-    pub fn set_scope_with_value(&self, key: &'a str, val: Value) {
+    pub fn set_scope_with_value(&self, key: &str, val: Value) {
+        self.set_scope_origin(key, None);
         if let Some(scope) = Rc::clone(&self.scope_stack).borrow_mut().last_mut() {
             scope[key] = val;
             return;
@@ -827,17 +2218,319 @@ impl<'a> AutoDataContext<'a> {
         panic!("No data scope be found, need to add scope first")
     }
 
+    /// Remove the key from the innermost scope which defines it
+    /// @return (Index of the scope, value), it is put back by `restore_scope_value`
+    pub fn take_scope_value(&self, key: &str) -> Option<(usize, Value)> {
+        let mut scope_stack = self.scope_stack.borrow_mut();
+        scope_stack
+            .iter_mut()
+            .enumerate()
+            .rev()
+            .find_map(|(idx, scope)| Some((idx, scope.as_object_mut()?.remove(key)?)))
+    }
+
+    pub fn restore_scope_value(&self, key: &str, (scope_idx, val): (usize, Value)) {
+        self.scope_stack.borrow_mut()[scope_idx][key] = val;
+    }
+
     pub fn set_sys(&mut self, key: &'a str, val: String) {
         self.sys.insert(key, val);
     }
 
+    /// Fill with a context which only has the data, and the render states
+    /// (for example indent, macros and blocks) are kept
+    /// @param origin Data path of the data, like `table` of 'with table'
+    pub fn fill_with_data<T>(
+        &mut self,
+        data: &Value,
+        origin: Option<String>,
+        fill_content: impl FnOnce(&mut AutoDataContext) -> T,
+    ) -> T {
+        let mut data_ctx = AutoDataContext::new(data, self.options);
+        data_ctx.sys.clone_from(&self.sys);
+        data_ctx.include_stack = Rc::clone(&self.include_stack);
+        data_ctx.blocks = Rc::clone(&self.blocks);
+        data_ctx.block_stack.clone_from(&self.block_stack);
+        data_ctx.macros.clone_from(&self.macros);
+        data_ctx.recursive_loops.clone_from(&self.recursive_loops);
+        data_ctx.pending_error = Rc::clone(&self.pending_error);
+        data_ctx.render_depth = self.render_depth;
+        data_ctx.macro_depth = self.macro_depth;
+        data_ctx.missing_count = self.missing_count;
+        data_ctx.recovery.clone_from(&self.recovery);
+        data_ctx.fill_depth = self.fill_depth;
+        data_ctx.detached_depth = self.detached_depth;
+        data_ctx.report.clone_from(&self.report);
+        data_ctx.data_origin = origin;
+        data_ctx.source_map.clone_from(&self.source_map);
+        data_ctx.captured_block = self.captured_block.take();
+        data_ctx.files = std::mem::take(&mut self.files);
+        for key in ["tag_indent", "tag_inline", "indent_base"] {
+            if let Some(val) = self.get_string(key) {
+                data_ctx.set_scope_with_string(key, val);
+            }
+        }
+        let filled = fill_content(&mut data_ctx);
+        self.captured_block = data_ctx.captured_block.take();
+        self.files = data_ctx.files;
+        self.error = data_ctx.error;
+        self.missing_count = data_ctx.missing_count;
+        filled
+    }
+
     pub fn push_scope(&self) {
         Rc::clone(&self.scope_stack).borrow_mut().push(json!({}));
+        self.scope_origins.borrow_mut().push(HashMap::new());
     }
 
     pub fn pop_scope(&mut self) {
         Rc::clone(&self.scope_stack).borrow_mut().pop();
+        self.scope_origins.borrow_mut().pop();
+    }
+
+    /// Data path of the key in the data of filling, like `table.columns[].name` of 'column.name' in the loop.
+    /// None if the usage is not reported or the key is a local variable, like the variable of 'set'
+    pub fn data_path(&self, key: &str) -> Option<String> {
+        self.report.as_ref()?;
+        let mut keys = key.split('.').filter(|k| !k.is_empty());
+        let root_key = keys.next()?;
+        let root_path = match self
+            .scope_stack
+            .borrow()
+            .iter()
+            .rposition(|scope| scope.get(root_key).is_some())
+        {
+            Some(idx) => self
+                .scope_origins
+                .borrow()
+                .get(idx)?
+                .get(root_key)?
+                .to_owned(),
+            None if self.sys.contains_key(key) => return None,
+            None => match &self.data_origin {
+                Some(origin) => format!("{}.{}", origin, root_key),
+                None => root_key.to_owned(),
+            },
+        };
+        Some(keys.fold(root_path, |path, k| format!("{}.{}", path, k)))
+    }
+
+    /// Data path of the expression if it is a variable
+    pub fn expression_path(&self, expression: &str) -> Option<String> {
+        match try_assess_expression(expression)? {
+            ExpressionType::VariableName => self.data_path(expression),
+            _ => None,
+        }
+    }
+
+    /// Set the data path of the scope variable which is set from the data, like the loop item
+    pub fn set_scope_origin(&self, key: &str, origin: Option<String>) {
+        if self.report.is_none() {
+            return;
+        }
+        if let Some(origins) = self.scope_origins.borrow_mut().last_mut() {
+            match origin {
+                Some(origin) => origins.insert(key.to_owned(), origin),
+                None => origins.remove(key),
+            };
+        }
+    }
+
+    /// Record the key is read, it is used in the report of data usage
+    pub fn mark_used(&self, key: &str) {
+        if let (Some(report), Some(path)) = (&self.report, self.data_path(key)) {
+            report.borrow_mut().mark_used(path);
+        }
+    }
+
+    /// Mark the origin of the token which is filled next if the source map is generated,
+    /// the template is the included template which is being filled
+    pub fn mark_origin(&self, filled: &mut Filled, template_bytes: &[u8], start: usize) {
+        let Some(source_map) = &self.source_map else {
+            return;
+        };
+        let (line, column) = line_column(template_bytes, start);
+        let mut source_map = source_map.borrow_mut();
+        let iterations = source_map.iterations.clone();
+        let origin_idx = source_map.record(TokenOrigin {
+            template: self.include_stack.borrow().last().cloned(),
+            line,
+            column,
+            iterations,
+        });
+        filled.mark(origin_idx);
+    }
+
+    /// Record the array is iterated, its items are not read as a whole
+    pub fn mark_iterated(&self, array_path: Option<&str>) {
+        if let (Some(report), Some(array_path)) = (&self.report, array_path) {
+            report.borrow_mut().mark_iterated(array_path.to_owned());
+        }
+    }
+
+    /// Record the run of the loop tag at the start of template
+    /// @return Index of the loop stat, None if the usage is not reported
+    pub fn record_loop(
+        &self,
+        template_bytes: &[u8],
+        start: usize,
+        expression: impl FnOnce() -> String,
+        iterations: usize,
+    ) -> Option<usize> {
+        let report = self.report.as_ref()?;
+        let template = self.include_stack.borrow().last().cloned();
+        let mut report = report.borrow_mut();
+        let index = report.loop_index(template.clone(), start, || {
+            let (line, column) = line_column(template_bytes, start);
+            LoopStat {
+                expression: expression(),
+                template,
+                line,
+                column,
+                runs: 0,
+                iterations: 0,
+            }
+        });
+        report.record_loop_run(index, iterations);
+        Some(index)
+    }
+
+    /// Abort the filling with the message, or record it as an error in the recovery mode,
+    /// it is located at the token which is being filled
+    /// @return Marker which is filled at the failed point
+    pub fn fail(&self, message: String) -> String {
+        let marker = format!("{{{{Error: {}}}}}", message);
+        match &self.recovery {
+            Some(recovery) => {
+                recovery
+                    .borrow_mut()
+                    .pending
+                    .push((self.fill_depth, Severity::Error, message))
+            }
+            // Only the first failure aborts the filling
+            None => {
+                self.pending_error
+                    .borrow_mut()
+                    .get_or_insert((self.fill_depth, message));
+            }
+        }
+        marker
+    }
+
+    /// The filling stops if it is failed out of the recovery mode, or aborted by the tags 'assert' and 'error'
+    pub fn is_aborted(&self) -> bool {
+        self.error.is_some() || self.pending_error.borrow().is_some()
+    }
+
+    /// Locate the failure of the current depth at the start of the template,
+    /// and it becomes the error which aborts the filling
+    pub fn locate_error(&mut self, template_bytes: &[u8], start: usize) {
+        let pending_error = self
+            .pending_error
+            .borrow_mut()
+            .take_if(|(depth, _)| *depth >= self.fill_depth);
+        if let Some((_, message)) = pending_error {
+            self.error = Some(new_fill_error(self, template_bytes, start, message));
+        }
+    }
+
+    /// Record the problem which is tolerated by the filling, it is ignored out of the recovery mode
+    pub fn warn(&self, message: String) {
+        if let Some(recovery) = &self.recovery {
+            recovery
+                .borrow_mut()
+                .pending
+                .push((self.fill_depth, Severity::Warning, message));
+        }
+    }
+
+    /// Locate the pending problems of the current depth at the span of the template
+    pub fn locate_problems(&self, template_bytes: &[u8], span: (usize, usize)) {
+        let Some(recovery) = &self.recovery else {
+            return;
+        };
+        let mut recovery = recovery.borrow_mut();
+        if recovery.pending.is_empty() {
+            return;
+        }
+        let (line, column) = line_column(template_bytes, span.0);
+        let template = self.include_stack.borrow().last().cloned();
+        let pending = std::mem::take(&mut recovery.pending);
+        for (depth, severity, message) in pending {
+            if depth < self.fill_depth {
+                recovery.pending.push((depth, severity, message));
+                continue;
+            }
+            recovery.diagnostics.push(Diagnostic {
+                severity,
+                message,
+                span: Span::new(span.0, span.1),
+                line,
+                column,
+                hint: None,
+                template: template.clone(),
+            });
+        }
+    }
+
+    /// Fill the text out of the template, like the message of 'error' or the value of filter 'render',
+    /// and its problems are located at the token which is being filled
+    pub fn fill_detached<T>(&mut self, fill_content: impl FnOnce(&mut Self) -> T) -> T {
+        self.detached_depth += 1;
+        let filled = fill_content(self);
+        self.detached_depth -= 1;
+        filled
+    }
+}
+
+/// Generate tokens of the template, and its problems are recorded in the recovery mode,
+/// otherwise the first error aborts the filling
+/// @param template Name of the included template, None if it is the filled template
+fn parse_template_in(
+    data_ctx: &mut AutoDataContext,
+    template_bytes: &[u8],
+    template: Option<&str>,
+) -> TemplateASTable {
+    let (template_ast, problems) = generate_tokens_0(template_bytes, data_ctx.options, true);
+    let Some(recovery) = &data_ctx.recovery else {
+        if let Some(problem) = problems
+            .into_iter()
+            .find(|problem| problem.severity == Severity::Error)
+        {
+            let (line, column) = line_column(template_bytes, problem.span.0);
+            data_ctx.error.get_or_insert(FillError {
+                message: problem.message,
+                template: template.map(str::to_owned),
+                line,
+                column,
+            });
+        }
+        return template_ast;
+    };
+    let template_content = bytes_to_str(template_bytes, 0, template_bytes.len());
+    recovery
+        .borrow_mut()
+        .diagnostics
+        .extend(problems.into_iter().map(|problem| Diagnostic {
+            template: template.map(str::to_owned),
+            ..Diagnostic::new(template_content, problem)
+        }));
+    template_ast
+}
+
+/// Generate tokens of the text out of the template, like the message of 'error',
+/// and its problems are located at the token which is being filled
+fn parse_text_in(data_ctx: &AutoDataContext, text_bytes: &[u8]) -> TemplateASTable {
+    let (text_ast, problems) = generate_tokens_0(text_bytes, data_ctx.options, true);
+    for problem in problems {
+        match problem.severity {
+            Severity::Error => {
+                data_ctx.fail(problem.message);
+            }
+            Severity::Warning => data_ctx.warn(problem.message),
+        }
     }
+    text_ast
 }
 
 fn fill(
@@ -846,22 +2539,64 @@ fn fill(
     data_ctx: &mut AutoDataContext,
     is_tag_fill: bool,
     is_need_set_env: bool,
-) -> String {
+) -> Filled {
+    // The problems of detached text are located at the token out of it
+    let is_located = data_ctx.detached_depth == 0;
+    if is_located {
+        data_ctx.fill_depth += 1;
+    }
+    let filled = fill_lines(
+        template_bytes,
+        template_ast,
+        data_ctx,
+        is_tag_fill,
+        is_need_set_env,
+        is_located,
+    );
+    if is_located {
+        data_ctx.fill_depth -= 1;
+    }
+    filled
+}
+
+fn fill_lines(
+    template_bytes: &[u8],
+    template_ast: &TemplateASTable,
+    data_ctx: &mut AutoDataContext,
+    is_tag_fill: bool,
+    is_need_set_env: bool,
+    is_located: bool,
+) -> Filled {
     if is_need_set_env {
         for env in &template_ast.custom_envs {
-            let (k, v) = get_kv_from_env_define(template_bytes, env.start, env.end, &data_ctx);
-            if let Some(decoded_v) = unicode_escape(&v) {
-                data_ctx.set_scope_with_string(k, decoded_v);
+            if let Some((k, v)) =
+                get_kv_from_env_define(template_bytes, env.start, env.end, data_ctx)
+            {
+                if let Some(decoded_v) = unicode_escape(data_ctx, &v) {
+                    data_ctx.set_scope_with_string(k, decoded_v);
+                }
+            }
+            if is_located {
+                data_ctx.locate_problems(template_bytes, env.span);
+                data_ctx.locate_error(template_bytes, env.span.0);
             }
         }
     }
 
+    // The first line of tag which is not first in row is not indented, and its whitespaces are kept,
+    // for example 'x = {% if a %} 1{% endif %}'
+    let is_inline_tag_fill =
+        is_tag_fill && data_ctx.get_string("tag_inline").as_deref() == Some("true");
     // Fill each line
-    let mut filled = String::new();
+    let mut filled = Filled::default();
+    // (filled length after the line feed, line feed length) of the last filled line feed
+    let mut last_line_feed: Option<(usize, usize)> = None;
     for (line_idx, line) in template_ast.syntax_lines.iter().enumerate() {
         // Fill indent
         let min_indent_len = template_ast.min_indent_len.unwrap_or(0);
-        let indent_filled = if is_tag_fill {
+        let indent_filled = if is_inline_tag_fill && line_idx == 0 {
+            line.get_indent(template_bytes)
+        } else if is_tag_fill {
             get_indent_in_tag(template_bytes, data_ctx, line, min_indent_len)
         } else {
             line.get_indent(template_bytes)
@@ -869,23 +2604,33 @@ fn fill(
         // Fill token in line
         let mut filled_count = 0;
         for (token_idx, token) in line.tokens.iter().enumerate() {
+            let token_ctx = match token {
+                Token::Text(token_ctx)
+                | Token::Placeholder(token_ctx)
+                | Token::Tag(token_ctx, _) => token_ctx,
+            };
+            if token_idx == 0 && !matches!(token, Token::Tag(..)) {
+                if let Some(ref indent) = indent_filled {
+                    filled.push_str(indent);
+                }
+            }
+            // The origin of source map is marked after the indent, and it is dropped if the token fills nothing
+            let before_fill_len = filled.len();
+            if is_located {
+                data_ctx.mark_origin(&mut filled, template_bytes, token_ctx.head_start);
+            }
             let is_filled = match token {
                 Token::Text(token_ctx) => {
-                    if token_idx == 0 {
-                        if let Some(ref indent) = indent_filled {
-                            filled.push_str(indent);
-                        }
-                    }
                     fill_text(&mut filled, template_bytes, token_idx, data_ctx, token_ctx)
                 }
-                Token::Placeholder(token_ctx) => {
-                    if token_idx == 0 {
-                        if let Some(ref indent) = indent_filled {
-                            filled.push_str(indent);
-                        }
-                    }
-                    fill_placeholder(&mut filled, template_bytes, token_idx, data_ctx, token_ctx)
-                }
+                Token::Placeholder(token_ctx) => fill_placeholder(
+                    &mut filled,
+                    template_bytes,
+                    token_idx,
+                    data_ctx,
+                    token_ctx,
+                    indent_filled.as_deref(),
+                ),
                 Token::Tag(token_ctx, ext) => fill_tag(
                     &mut filled,
                     template_bytes,
@@ -897,12 +2642,31 @@ fn fill(
                     min_indent_len,
                 ),
             };
+            if filled.len() == before_fill_len {
+                filled.truncate(before_fill_len);
+            }
             if is_filled {
                 filled_count += 1;
             }
+            if is_located {
+                data_ctx
+                    .locate_problems(template_bytes, (token_ctx.head_start, token_ctx.tail_end));
+                data_ctx.locate_error(template_bytes, token_ctx.head_start);
+            }
+            if data_ctx.is_aborted() {
+                return filled;
+            }
         }
         // No line feed fill: only Token::Tag in line (No contains tag's sub token) and no content be filled
         if line.visible_token_count() == 1 && line.tag_token_cnt > 0 && filled_count == 0 {
+            // The line feed of previous line becomes the last content of Token::Tag, drop it
+            if is_tag_fill && line_idx == template_ast.syntax_lines.len() - 1 {
+                if let Some((filled_len, line_feed_len)) = last_line_feed {
+                    if filled_len == filled.len() {
+                        filled.truncate(filled_len - line_feed_len);
+                    }
+                }
+            }
             continue;
         }
         // No line feed fill: Token::Tag's last sub token
@@ -912,10 +2676,12 @@ fn fill(
         // Fill line feed
         if line.is_valid_line() {
             if let Some(line_feed) = &line.line_feed {
-                match line_feed {
-                    LineFeed::LF => filled.push_str("\n"),
-                    LineFeed::CRLF => filled.push_str("\r\n"),
-                }
+                let line_feed = match line_feed {
+                    LineFeed::LF => "\n",
+                    LineFeed::CRLF => "\r\n",
+                };
+                filled.push_str(line_feed);
+                last_line_feed = Some((filled.len(), line_feed.len()));
             }
         }
     }
@@ -924,7 +2690,7 @@ fn fill(
 
 /// @return Content be filled or not
 fn fill_text(
-    filled: &mut String,
+    filled: &mut Filled,
     template_bytes: &[u8],
     _token_idx: usize,
     _data_ctx: &mut AutoDataContext,
@@ -936,24 +2702,257 @@ fn fill_text(
 
 /// @return Content be filled or not
 fn fill_placeholder(
-    filled: &mut String,
+    filled: &mut Filled,
     template_bytes: &[u8],
     _token_idx: usize,
     data_ctx: &mut AutoDataContext,
     token_ctx: &TokenContext,
+    line_indent: Option<&str>,
 ) -> bool {
     let placeholder = bytes_to_str(template_bytes, token_ctx.start, token_ctx.end).trim();
-    let replaced = match data_ctx.get_string(placeholder) {
-        Some(v) => v,
-        None => format!("{{{{{}: Not found}}}}", placeholder),
+    // {{ name | filter_1 | filter_2(arg_1, arg_2) }}
+    let mut parts = split_outside_quotes(placeholder, '|').into_iter();
+    let name = parts.next().unwrap_or_default().trim();
+    // Call, like 'super()' or 'macro_name(arg_1, arg_2)'
+    let call = name
+        .strip_suffix(')')
+        .and_then(|call| call.split_once('('))
+        .map(|(callee, args)| (callee.trim(), args));
+    let value = match (call, try_assess_expression(name)) {
+        (Some(("super", _)), _) => Some(fill_super(data_ctx, line_indent)),
+        (Some(("loop", args)), _) => Some(fill_recursive_loop(data_ctx, args, line_indent)),
+        (Some((callee, args)), _) => data_ctx
+            .macros
+            .get(callee)
+            .cloned()
+            .map(|macro_define| fill_macro(data_ctx, callee, &macro_define, args, line_indent)),
+        (None, Some(name_type)) => {
+            get_expression_result(data_ctx, &name_type, name).map(Filled::from)
+        }
+        (None, None) => data_ctx.get_string(name).map(Filled::from),
+    };
+    let replaced = match value {
+        Some(mut v) => {
+            for filter in parts {
+                v = apply_filter(data_ctx, filter.trim(), v, line_indent);
+            }
+            // The content of call has been indented
+            if data_ctx.options.auto_indent && call.is_none() {
+                v = indent_continuation_lines(v, line_indent.unwrap_or_default());
+            }
+            v
+        }
+        None => {
+            match call {
+                Some((callee, _)) => data_ctx.warn(format!("Macro not found: {}", callee)),
+                None => data_ctx.warn(format!("Variable not found: {}", name)),
+            }
+            if let Some(report) = &data_ctx.report {
+                report.borrow_mut().record_missing(placeholder);
+            }
+            data_ctx.missing_count += 1;
+            Filled::from(format!("{{{{{}: Not found}}}}", placeholder))
+        }
     };
-    filled.push_str(&replaced);
+    filled.push(replaced);
     true
 }
 
+/// Names of the filters which are supported by `apply_filter`
+const FILTER_NAMES: [&str; 2] = ["indent", "render"];
+
+/// Filter is defined like 'name' or 'name(arg_1, arg_2)'
+fn apply_filter(
+    data_ctx: &mut AutoDataContext,
+    filter: &str,
+    value: Filled,
+    line_indent: Option<&str>,
+) -> Filled {
+    let (name, args) = match filter.split_once('(') {
+        Some((name, args)) => {
+            let Some(args) = args.strip_suffix(')') else {
+                return data_ctx.fail(format!("Illegal filter: {}", filter)).into();
+            };
+            let mut arg_values: Vec<Option<String>> = Vec::new();
+            for arg in split_outside_quotes(args, ',').into_iter().map(str::trim) {
+                if arg.is_empty() {
+                    continue;
+                }
+                let Some(arg_type) = try_assess_expression(arg) else {
+                    return data_ctx
+                        .fail(format!("Unvalid variable name: {}", arg))
+                        .into();
+                };
+                arg_values.push(get_expression_result(data_ctx, &arg_type, arg));
+            }
+            (name.trim(), arg_values)
+        }
+        None => (filter, Vec::new()),
+    };
+    match name {
+        // indent: indent of the line where the placeholder is
+        // indent(4): 4 spaces
+        // indent("\t"): the text
+        "indent" => {
+            let indent = match args.first() {
+                Some(Some(arg)) => match arg.parse::<usize>() {
+                    Ok(width) => " ".repeat(width),
+                    Err(_) => arg.to_owned(),
+                },
+                _ => line_indent.unwrap_or_default().to_owned(),
+            };
+            indent_continuation_lines(value, &indent)
+        }
+        // render: fill the value as a template with the current data scope
+        "render" => fill_render(data_ctx, &value.text),
+        _ => data_ctx
+            .fail(format!("Unsupported filter: {}", name))
+            .into(),
+    }
+}
+
+/// Check the placeholder like the filling without data, like 'name | indent(4)'.
+/// The illegal name is a warning which is filled as not found, and the illegal filter is an error
+/// @return Problem if the name or a filter is illegal
+pub(crate) fn check_placeholder(placeholder: &str, span: (usize, usize)) -> Option<LintProblem> {
+    let problem = |message: String, severity: Severity, hint: Option<String>| {
+        Some(LintProblem {
+            message,
+            span,
+            severity,
+            hint,
+        })
+    };
+    let mut parts = split_outside_quotes(placeholder.trim(), '|').into_iter();
+    let name = parts.next().unwrap_or_default().trim();
+    let name_result = match name.strip_suffix(')').and_then(|call| call.split_once('(')) {
+        Some((callee, args)) => match try_assess_expression(callee.trim()) {
+            Some(ExpressionType::VariableName) => check_args(args),
+            _ => Err(format!("Illegal call: {}", name)),
+        },
+        None => assess_tag_expression(name).map(|_| ()),
+    };
+    if let Err(message) = name_result {
+        return problem(message, Severity::Warning, Some(expression_hint()));
+    }
+    for filter in parts {
+        let filter = filter.trim();
+        let name = match filter.split_once('(') {
+            Some((name, args)) => {
+                let Some(args) = args.strip_suffix(')') else {
+                    return problem(
+                        format!("Illegal filter: {}", filter),
+                        Severity::Error,
+                        Some(String::from("Close the arguments with `)`")),
+                    );
+                };
+                if let Err(message) = check_args(args) {
+                    return problem(message, Severity::Error, Some(expression_hint()));
+                }
+                name.trim()
+            }
+            None => filter,
+        };
+        if !FILTER_NAMES.contains(&name) {
+            let hint = match closest_name(name, FILTER_NAMES) {
+                Some(similar) => format!("Did you mean `{}`?", similar),
+                None => format!("Supported filters: {}", FILTER_NAMES.join(", ")),
+            };
+            return problem(
+                format!("Unsupported filter: {}", name),
+                Severity::Error,
+                Some(hint),
+            );
+        }
+    }
+    None
+}
+
+fn expression_hint() -> String {
+    String::from("A variable starts with a letter, `_` or `$`, and a string is quoted by `\"`")
+}
+
+/// Arguments like '"id", type, nullable=false'
+fn check_args(args: &str) -> Result<(), String> {
+    for arg in split_outside_quotes(args, ',').into_iter().map(str::trim) {
+        let value = match arg.split_once('=') {
+            Some((name, value)) if !name.contains('"') => value.trim(),
+            _ => arg,
+        };
+        if !value.is_empty() {
+            assess_tag_expression(value)?;
+        }
+    }
+    Ok(())
+}
+
+/// Fill the value as a template, and its variables and tags are not visible out of it
+fn fill_render(data_ctx: &mut AutoDataContext, content: &str) -> Filled {
+    let depth = data_ctx.render_depth + 1;
+    if depth > data_ctx.options.recursion_limit {
+        return data_ctx
+            .fail(format!(
+                "Recursion limit exceeded: {}",
+                data_ctx.options.recursion_limit
+            ))
+            .into();
+    }
+    let bytes = content.as_bytes();
+    let template_ast = parse_text_in(data_ctx, bytes);
+    data_ctx.render_depth = depth;
+    data_ctx.push_scope();
+    let rendered =
+        data_ctx.fill_detached(|data_ctx| fill(bytes, &template_ast, data_ctx, false, true));
+    data_ctx.pop_scope();
+    data_ctx.render_depth = depth - 1;
+    rendered
+}
+
+/// Indent each line except the first line, and the blank line will not be indented
+fn indent_continuation_lines(value: Filled, indent: &str) -> Filled {
+    if indent.is_empty() || !value.text.contains('\n') {
+        return value;
+    }
+    let mut line_start = 0;
+    let edits: Vec<_> = value
+        .text
+        .split('\n')
+        .enumerate()
+        .filter_map(|(idx, line)| {
+            let start = line_start;
+            line_start += line.len() + 1;
+            (idx > 0 && !line.trim().is_empty()).then(|| (start..start, indent.to_owned()))
+        })
+        .collect();
+    value.edit(edits)
+}
+
+/// Split the text by the separator which is not in '"'
+pub(crate) fn split_outside_quotes(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (idx, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            _ if c == separator && !in_quotes => {
+                parts.push(&text[start..idx]);
+                start = idx + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
 /// @return Content be filled or not
+#[allow(clippy::too_many_arguments)]
 fn fill_tag(
-    filled: &mut String,
+    filled: &mut Filled,
     template_bytes: &[u8],
     line: &SyntaxLine,
     token_idx: usize,
@@ -974,63 +2973,852 @@ fn fill_tag(
     );
 
     let before_fill_len = filled.len();
+    // (name, value) of the variable which is set out of the tag
+    let mut assignment = None;
     match &tag_ext.tag {
-        Tag::For(item_name, array_name) => {
-            if let Some(array) = data_ctx.get_array(&array_name) {
-                // Set Tag::For public env variables
-                data_ctx.set_scope_with_string("$max", (array.len() - 1).to_string());
-                for env in &tag_ext.sub_ast.custom_envs {
-                    let (k, v) =
-                        get_kv_from_env_define(template_bytes, env.start, env.end, &data_ctx);
-                    if let Some(decoded_v) = unicode_escape(&v) {
-                        data_ctx.set_scope_with_string(k, decoded_v);
+        Tag::For(item_name, value_name, array_name, recursive) => {
+            let array = match value_name {
+                None => {
+                    match data_ctx.get_value(array_name) {
+                        None => data_ctx.warn(format!("Variable not found: {}", array_name)),
+                        Some(Value::Array(_)) => {}
+                        Some(_) => {
+                            data_ctx.warn(format!("Variable is not an array: {}", array_name))
+                        }
                     }
+                    data_ctx.get_array(array_name)
                 }
-                // Polling processing
-                let join_with = data_ctx.get_string("join_with");
-                for i in 0..array.len() {
-                    // The scope of variables for each polling
-                    data_ctx.push_scope();
-                    data_ctx.set_scope_with_string("$index", i.to_string());
-                    let item = array.get(i).unwrap();
-                    data_ctx.set_scope_with_value(&item_name, item.clone());
-
-                    let replaced = fill(template_bytes, &tag_ext.sub_ast, data_ctx, true, false);
-                    filled.push_str(&replaced);
-
-                    if join_with.is_some() && i < array.len() - 1 {
-                        filled.push_str(join_with.as_ref().unwrap());
+                // The entries of object are iterated as the pairs of [key, value]
+                Some(_) => match data_ctx.get_value(array_name) {
+                    Some(Value::Object(object)) => Some(
+                        object
+                            .into_iter()
+                            .map(|(key, value)| Value::Array(vec![Value::String(key), value]))
+                            .collect(),
+                    ),
+                    None => {
+                        data_ctx.warn(format!("Variable not found: {}", array_name));
+                        None
                     }
-                    data_ctx.pop_scope();
+                    Some(_) => {
+                        data_ctx.warn(format!("Variable is not an object: {}", array_name));
+                        None
+                    }
+                },
+            };
+            let stat_index = data_ctx.record_loop(
+                template_bytes,
+                token_ctx.head_start,
+                || match value_name {
+                    Some(value_name) => format!("{}, {} in {}", item_name, value_name, array_name),
+                    None => format!("{} in {}", item_name, array_name),
+                },
+                array.as_ref().map_or(0, Vec::len),
+            );
+            if let Some(array) = array {
+                if *recursive {
+                    data_ctx.recursive_loops.push(RecursiveLoop {
+                        source: Rc::from(bytes_to_str(template_bytes, 0, template_bytes.len())),
+                        item_name: item_name.to_owned(),
+                        sub_ast: Rc::new(tag_ext.sub_ast.clone()),
+                        depth: 0,
+                        stat_index,
+                    });
+                    data_ctx.set_scope_with_string("$depth", 0.to_string());
+                }
+                let array_path = data_ctx.data_path(array_name);
+                let replaced = fill_for(
+                    template_bytes,
+                    &tag_ext.sub_ast,
+                    data_ctx,
+                    item_name,
+                    value_name.as_deref(),
+                    &array,
+                    array_path.as_deref(),
+                );
+                filled.push(replaced);
+                if *recursive {
+                    data_ctx.recursive_loops.pop();
                 }
             }
         }
-        Tag::If(left_type, left, operator, right_type, right) => {
-            let left = get_expression_result(&data_ctx, left_type, &left);
-            let right = get_expression_result(&data_ctx, right_type, &right);
-            match operator.as_str() {
-                "==" => {
-                    if left.is_some() && right.is_some() && left.unwrap() == right.unwrap() {
-                        let replaced = fill(template_bytes, &tag_ext.sub_ast, data_ctx, true, true);
-                        filled.push_str(&replaced);
-                    }
+        Tag::If(condition) => {
+            if is_condition_true(data_ctx, condition) {
+                let replaced = fill(template_bytes, &tag_ext.sub_ast, data_ctx, true, true);
+                filled.push(replaced);
+            }
+        }
+        Tag::Assert(condition, message_type, message) => {
+            if !is_condition_true(data_ctx, condition) {
+                let message = fill_message(data_ctx, message_type, message);
+                abort_filling(filled, data_ctx, template_bytes, token_ctx, message);
+            }
+        }
+        Tag::Error(message_type, message) => {
+            let message = fill_message(data_ctx, message_type, message);
+            abort_filling(filled, data_ctx, template_bytes, token_ctx, message);
+        }
+        Tag::Block(name) => {
+            let replaced = fill_block(data_ctx, name, 0)
+                .unwrap_or_else(|| fill(template_bytes, &tag_ext.sub_ast, data_ctx, true, true));
+            if let Some((captured_name, captured @ None)) = &mut data_ctx.captured_block {
+                if captured_name == name {
+                    *captured = Some(replaced.clone());
                 }
-                "!=" => {
-                    if left.is_none() || right.is_none() || left.unwrap() != right.unwrap() {
-                        let replaced = fill(template_bytes, &tag_ext.sub_ast, data_ctx, true, true);
-                        filled.push_str(&replaced);
-                    }
+            }
+            filled.push(replaced);
+        }
+        Tag::Set(name) => {
+            // The captured content is not indented by the tag
+            data_ctx.set_scope_with_string("tag_indent", String::new());
+            data_ctx.set_scope_with_string("tag_inline", false.to_string());
+            let captured = fill(template_bytes, &tag_ext.sub_ast, data_ctx, true, true);
+            assignment = Some((name, captured.text));
+        }
+        Tag::File(path_type, path) => match get_expression_result(data_ctx, path_type, path) {
+            Some(path) => 'file: {
+                // Placeholders in path, the file is not emitted if any of them is not found
+                let path_bytes = path.as_bytes();
+                let path_ast = parse_text_in(data_ctx, path_bytes);
+                let missing_count = data_ctx.missing_count;
+                let filled_path = data_ctx.fill_detached(|data_ctx| {
+                    fill(path_bytes, &path_ast, data_ctx, false, true).text
+                });
+                if data_ctx.missing_count > missing_count {
+                    let message = format!("File path placeholder not found: {}", path);
+                    filled.push_str(&data_ctx.fail(message));
+                    break 'file;
+                }
+                // The file content is not indented by the tag
+                data_ctx.set_scope_with_string("tag_indent", String::new());
+                data_ctx.set_scope_with_string("tag_inline", false.to_string());
+                let content = fill(template_bytes, &tag_ext.sub_ast, data_ctx, true, true);
+                data_ctx.files.push((filled_path, content.text));
+            }
+            None => filled.push_str(&data_ctx.fail(format!("File path not found: {}", path))),
+        },
+        Tag::Match(subject_type, subject) => {
+            if *subject_type == ExpressionType::VariableName {
+                data_ctx.mark_used(subject);
+            }
+            let subject = get_typed_expression_result(data_ctx, subject_type, subject);
+            // Arms are the Token::Tag in sub tokens, and other sub tokens are ignored
+            let arms: Vec<&TagExtend> = tag_ext
+                .sub_ast
+                .syntax_lines
+                .iter()
+                .flat_map(|line| &line.tokens)
+                .filter_map(|token| match token {
+                    Token::Tag(_, arm) => Some(arm),
+                    _ => None,
+                })
+                .collect();
+            let arm = arms
+                .iter()
+                .find(|arm| match &arm.tag {
+                    Tag::Case(values) => values.iter().any(|(value_type, value)| {
+                        is_value_equal(
+                            &subject,
+                            &get_typed_expression_result(data_ctx, value_type, value),
+                        )
+                    }),
+                    _ => false,
+                })
+                .or_else(|| arms.iter().find(|arm| matches!(arm.tag, Tag::Default)));
+            if let Some(arm) = arm {
+                let replaced = fill(template_bytes, &arm.sub_ast, data_ctx, true, true);
+                filled.push(replaced);
+            }
+        }
+        Tag::With(WithScope::Bindings(bindings)) => {
+            for (name, expression) in bindings {
+                let value = get_expression_value(data_ctx, expression);
+                let origin = data_ctx.expression_path(expression);
+                data_ctx.set_scope_with_value(name, value);
+                data_ctx.set_scope_origin(name, origin);
+            }
+            let replaced = fill(template_bytes, &tag_ext.sub_ast, data_ctx, true, true);
+            filled.push(replaced);
+        }
+        Tag::With(WithScope::Data(name)) => match data_ctx.get_value(name) {
+            Some(data) => {
+                let origin = data_ctx.data_path(name);
+                let replaced = data_ctx.fill_with_data(&data, origin, |with_ctx| {
+                    fill(template_bytes, &tag_ext.sub_ast, with_ctx, true, true)
+                });
+                filled.push(replaced);
+            }
+            None => filled.push_str(&data_ctx.fail(format!("With data not found: {}", name))),
+        },
+        // Filled as its parent template before filling
+        Tag::Extends(..) => {}
+        // Filled when it is called
+        Tag::Macro(..) => {}
+        Tag::Import(name_type, name, alias) => 'import: {
+            let Some(name) = get_expression_result(data_ctx, name_type, name) else {
+                let marker = data_ctx.fail(format!("Import template name not found: {}", name));
+                filled.push_str(&marker);
+                break 'import;
+            };
+            let content = match load_template(data_ctx.options, &name) {
+                Ok(content) => content,
+                Err(message) => {
+                    filled.push_str(&data_ctx.fail(message));
+                    break 'import;
                 }
-                _ => panic!("Unsupported if's operator: {}", operator),
+            };
+            let template_ast = parse_template_in(data_ctx, content.as_bytes(), Some(&name));
+            let source = Rc::from(content);
+            let mut macros = HashMap::new();
+            collect_macros(&source, &template_ast, &mut macros);
+            for (macro_name, macro_define) in macros {
+                let macro_name = match alias {
+                    Some(alias) => format!("{}.{}", alias, macro_name),
+                    None => macro_name,
+                };
+                data_ctx.macros.insert(macro_name, macro_define);
             }
         }
+        Tag::Include(name_type, name, with) => {
+            let replaced = match get_expression_result(data_ctx, name_type, name) {
+                Some(name) => fill_include(data_ctx, &name, with.as_deref()),
+                None => data_ctx
+                    .fail(format!("Include template name not found: {}", name))
+                    .into(),
+            };
+            filled.push(replaced);
+        }
+        Tag::Custom(custom_tag, args) => {
+            let mut tag_ctx = TagContext {
+                template_bytes,
+                sub_ast: &tag_ext.sub_ast,
+                data_ctx,
+            };
+            // The rendered content is mapped to the tag, the origins in its body are not kept
+            let replaced = custom_tag.render(args, &mut tag_ctx);
+            filled.push_str(&replaced);
+        }
         _ => panic!("An impossible error when parse tag token"),
     }
     data_ctx.pop_scope();
+    if let Some((name, value)) = assignment {
+        data_ctx.set_scope_with_string(name, value);
+    }
     // Content be filled or not
     filled.len() > before_fill_len
 }
 
+/// Data context of the custom tag which is being filled
+pub struct TagContext<'a, 'b> {
+    template_bytes: &'a [u8],
+    sub_ast: &'a TemplateASTable,
+    data_ctx: &'a mut AutoDataContext<'b>,
+}
+
+impl TagContext<'_, '_> {
+    pub fn options(&self) -> &FillOptions {
+        self.data_ctx.options
+    }
+
+    /// Value of the expression, like `table.name`, `"abc"`, `123` or `true`, and the undefined variable is null
+    pub fn value(&self, expression: &str) -> Value {
+        if try_assess_expression(expression) == Some(ExpressionType::VariableName) {
+            self.data_ctx.mark_used(expression);
+        }
+        get_expression_value(self.data_ctx, expression)
+    }
+
+    /// Set the variable in the scope of the tag, it is visible in the body
+    pub fn set(&mut self, name: &str, value: Value) {
+        self.data_ctx.set_scope_with_value(name, value);
+    }
+
+    /// Fill the body of the block tag, it can be filled more than once
+    pub fn fill_body(&mut self) -> String {
+        fill(self.template_bytes, self.sub_ast, self.data_ctx, true, true).text
+    }
+
+    /// Fill the template content as the content of tag, with the current data scope
+    pub fn fill_content(&mut self, content: &str) -> String {
+        let bytes = content.as_bytes();
+        let template_ast = parse_text_in(self.data_ctx, bytes);
+        self.data_ctx
+            .fill_detached(|data_ctx| fill(bytes, &template_ast, data_ctx, true, true).text)
+    }
+}
+
+fn is_condition_true(data_ctx: &AutoDataContext, condition: &Condition) -> bool {
+    let left = get_expression_result(data_ctx, &condition.left_type, &condition.left);
+    match condition.operator.as_str() {
+        "is defined" => left.is_some(),
+        "is not defined" => left.is_none(),
+        operator => {
+            let right = get_expression_result(data_ctx, &condition.right_type, &condition.right);
+            // The undefined variable is false in the truthy condition like 'if nullable',
+            // and it is a problem in the comparison
+            let is_truthy =
+                condition.right_type == ExpressionType::Boolean && condition.right == "true";
+            for (expression_type, expression, result) in [
+                (&condition.left_type, &condition.left, &left),
+                (&condition.right_type, &condition.right, &right),
+            ] {
+                if *expression_type == ExpressionType::VariableName
+                    && result.is_none()
+                    && !is_truthy
+                {
+                    data_ctx.warn(format!("Variable not found in comparison: {}", expression));
+                }
+            }
+            match operator {
+                "==" => left.is_some() && right.is_some() && left.unwrap() == right.unwrap(),
+                "!=" => left.is_none() || right.is_none() || left.unwrap() != right.unwrap(),
+                _ => {
+                    data_ctx.fail(format!("Unsupported if's operator: {}", operator));
+                    false
+                }
+            }
+        }
+    }
+}
+
+/// Message of the tags 'assert' and 'error', its placeholders are filled
+fn fill_message(
+    data_ctx: &mut AutoDataContext,
+    message_type: &ExpressionType,
+    message: &str,
+) -> String {
+    let message = get_expression_result(data_ctx, message_type, message).unwrap_or_default();
+    let message_bytes = message.as_bytes();
+    let message_ast = parse_text_in(data_ctx, message_bytes);
+    data_ctx.fill_detached(|data_ctx| fill(message_bytes, &message_ast, data_ctx, false, true).text)
+}
+
+/// Abort the filling with the error at the token,
+/// or record the error and fill the marker in the recovery mode
+fn abort_filling(
+    filled: &mut Filled,
+    data_ctx: &mut AutoDataContext,
+    template_bytes: &[u8],
+    token_ctx: &TokenContext,
+    message: String,
+) {
+    if data_ctx.recovery.is_some() {
+        filled.push_str(&data_ctx.fail(message));
+    } else {
+        data_ctx.error = Some(new_fill_error(
+            data_ctx,
+            template_bytes,
+            token_ctx.head_start,
+            message,
+        ));
+    }
+}
+
+/// Error at the index of the template, the template is the included template which is being filled
+fn new_fill_error(
+    data_ctx: &AutoDataContext,
+    template_bytes: &[u8],
+    start: usize,
+    message: String,
+) -> FillError {
+    let (line, column) = line_column(template_bytes, start);
+    FillError {
+        message,
+        template: data_ctx.include_stack.borrow().last().cloned(),
+        line,
+        column,
+    }
+}
+
+/// @return (line, column in chars) of the index, start at 1
+pub(crate) fn line_column(template_bytes: &[u8], idx: usize) -> (usize, usize) {
+    let before = bytes_to_str(template_bytes, 0, idx);
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// Fill the template which is loaded by name with the current data scope,
+/// or with the `with` context as its data
+fn fill_include(data_ctx: &mut AutoDataContext, name: &str, with: Option<&str>) -> Filled {
+    let options = data_ctx.options;
+    let include_stack = Rc::clone(&data_ctx.include_stack);
+    if include_stack
+        .borrow()
+        .iter()
+        .any(|included| included == name)
+    {
+        let message = format!(
+            "Include cycle: {} -> {}",
+            include_stack.borrow().join(" -> "),
+            name
+        );
+        return data_ctx.fail(message).into();
+    }
+    let content = match load_template(options, name) {
+        Ok(content) => content,
+        Err(message) => return data_ctx.fail(message).into(),
+    };
+    let bytes = content.as_bytes();
+    let template_ast = parse_template_in(data_ctx, bytes, Some(name));
+    if data_ctx.is_aborted() {
+        return Filled::default();
+    }
+
+    let mut replaced = match with {
+        Some(with) => match data_ctx.get_value(with) {
+            Some(data) => {
+                let origin = data_ctx.data_path(with);
+                include_stack.borrow_mut().push(name.to_owned());
+                let replaced = data_ctx.fill_with_data(&data, origin, |include_ctx| {
+                    fill_template_tree(bytes, template_ast, include_ctx, true)
+                });
+                include_stack.borrow_mut().pop();
+                replaced
+            }
+            None => {
+                return data_ctx
+                    .fail(format!("Include context not found: {}", with))
+                    .into()
+            }
+        },
+        None => {
+            include_stack.borrow_mut().push(name.to_owned());
+            let replaced = fill_template_tree(bytes, template_ast, data_ctx, true);
+            include_stack.borrow_mut().pop();
+            replaced
+        }
+    };
+
+    // The line feed at the end of included template is replaced by the line feed of include tag
+    if let Some(text) = replaced.text.strip_suffix('\n') {
+        replaced.truncate(text.strip_suffix('\r').unwrap_or(text).len());
+    }
+    replaced
+}
+
+/// @return Message of the failure if the template can not be loaded
+fn load_template(options: &FillOptions, name: &str) -> Result<String, String> {
+    options
+        .loader
+        .as_ref()
+        .ok_or_else(|| format!("No template loader to load: {}", name))?
+        .load(name)
+        .map_err(|e| format!("Load template fail: {}, {}", name, e))
+}
+
+/// Fill the template, and the template which extends a parent template is filled as its parent
+/// with the blocks overridden by it (the content out of blocks is ignored)
+fn fill_template_tree(
+    template_bytes: &[u8],
+    template_ast: TemplateASTable,
+    data_ctx: &mut AutoDataContext,
+    is_tag_fill: bool,
+) -> Filled {
+    let mut source: Rc<str> = Rc::from(bytes_to_str(template_bytes, 0, template_bytes.len()));
+    let mut template_ast = template_ast;
+    let mut blocks = HashMap::new();
+    let mut macros = HashMap::new();
+    let mut extends_names: Vec<String> = Vec::new();
+    while let Some((name_type, name)) = find_extends(&template_ast) {
+        collect_blocks(&source, &template_ast, &mut blocks);
+        collect_macros(&source, &template_ast, &mut macros);
+        // The template is filled as itself if its parent can not be filled in the recovery mode
+        let Some(name) = get_expression_result(data_ctx, name_type, name) else {
+            data_ctx.fail(format!("Extends template name not found: {}", name));
+            break;
+        };
+        if extends_names.contains(&name) {
+            data_ctx.fail(format!(
+                "Extends cycle: {} -> {}",
+                extends_names.join(" -> "),
+                name
+            ));
+            break;
+        }
+        let content = match load_template(data_ctx.options, &name) {
+            Ok(content) => content,
+            Err(message) => {
+                data_ctx.fail(message);
+                break;
+            }
+        };
+        template_ast = parse_template_in(data_ctx, content.as_bytes(), Some(&name));
+        source = Rc::from(content);
+        extends_names.push(name);
+    }
+    collect_blocks(&source, &template_ast, &mut blocks);
+    collect_macros(&source, &template_ast, &mut macros);
+    // The filling is aborted by the parent template which can not be filled
+    if data_ctx.is_aborted() {
+        return Filled::default();
+    }
+
+    let outer_blocks = std::mem::replace(&mut data_ctx.blocks, Rc::new(blocks));
+    // The macros of caller are available, and they are overridden by the template's macros
+    let mut all_macros = data_ctx.macros.clone();
+    all_macros.extend(macros);
+    let outer_macros = std::mem::replace(&mut data_ctx.macros, all_macros);
+    let filled = fill(
+        source.as_bytes(),
+        &template_ast,
+        data_ctx,
+        is_tag_fill,
+        true,
+    );
+    data_ctx.blocks = outer_blocks;
+    data_ctx.macros = outer_macros;
+    filled
+}
+
+/// Tag 'extends' is only available out of other tags
+fn find_extends(template_ast: &TemplateASTable) -> Option<(&ExpressionType, &str)> {
+    template_ast
+        .syntax_lines
+        .iter()
+        .flat_map(|line| &line.tokens)
+        .find_map(|token| match token {
+            Token::Tag(_, ext) => match &ext.tag {
+                Tag::Extends(name_type, name) => Some((name_type, name.as_str())),
+                _ => None,
+            },
+            _ => None,
+        })
+}
+
+fn collect_blocks(
+    source: &Rc<str>,
+    template_ast: &TemplateASTable,
+    blocks: &mut HashMap<String, Vec<BlockDefine>>,
+) {
+    for token in template_ast
+        .syntax_lines
+        .iter()
+        .flat_map(|line| &line.tokens)
+    {
+        if let Token::Tag(_, ext) = token {
+            if let Tag::Block(name) = &ext.tag {
+                blocks
+                    .entry(name.to_owned())
+                    .or_default()
+                    .push(BlockDefine {
+                        source: Rc::clone(source),
+                        sub_ast: ext.sub_ast.clone(),
+                    });
+            }
+            collect_blocks(source, &ext.sub_ast, blocks);
+        }
+    }
+}
+
+/// Fill the block define at `level`, 0 is the define of the last child template
+fn fill_block(data_ctx: &mut AutoDataContext, name: &str, level: usize) -> Option<Filled> {
+    let blocks = Rc::clone(&data_ctx.blocks);
+    let block = blocks.get(name)?.get(level)?;
+    data_ctx.block_stack.push((name.to_owned(), level));
+    let replaced = fill(
+        block.source.as_bytes(),
+        &block.sub_ast,
+        data_ctx,
+        true,
+        true,
+    );
+    data_ctx.block_stack.pop();
+    Some(replaced)
+}
+
+/// The macro of child template is preferred, so the collected macro will not be replaced
+fn collect_macros(
+    source: &Rc<str>,
+    template_ast: &TemplateASTable,
+    macros: &mut HashMap<String, Rc<MacroDefine>>,
+) {
+    for token in template_ast
+        .syntax_lines
+        .iter()
+        .flat_map(|line| &line.tokens)
+    {
+        if let Token::Tag(_, ext) = token {
+            if let Tag::Macro(name, params) = &ext.tag {
+                macros.entry(name.to_owned()).or_insert_with(|| {
+                    Rc::new(MacroDefine {
+                        source: Rc::clone(source),
+                        params: params.clone(),
+                        sub_ast: ext.sub_ast.clone(),
+                    })
+                });
+            }
+            collect_macros(source, &ext.sub_ast, macros);
+        }
+    }
+}
+
+/// Fill the sub tokens of Tag::For for each item, the items are the pairs of [key, value]
+/// if the value name is given
+fn fill_for(
+    template_bytes: &[u8],
+    sub_ast: &TemplateASTable,
+    data_ctx: &mut AutoDataContext,
+    item_name: &str,
+    value_name: Option<&str>,
+    array: &[Value],
+    array_path: Option<&str>,
+) -> Filled {
+    data_ctx.mark_iterated(array_path);
+    let item_origin = array_path.map(|array_path| format!("{}[]", array_path));
+    let mut filled = Filled::default();
+    // Set Tag::For public env variables
+    data_ctx.set_scope_with_string("$max", array.len().saturating_sub(1).to_string());
+    for env in &sub_ast.custom_envs {
+        if let Some((k, v)) = get_kv_from_env_define(template_bytes, env.start, env.end, data_ctx) {
+            if let Some(decoded_v) = unicode_escape(data_ctx, &v) {
+                data_ctx.set_scope_with_string(k, decoded_v);
+            }
+        }
+    }
+    // Polling processing
+    let join_with = data_ctx.get_string("join_with");
+    for (i, item) in array.iter().enumerate() {
+        // The scope of variables for each polling
+        data_ctx.push_scope();
+        data_ctx.set_scope_with_string("$index", i.to_string());
+        match (value_name, item) {
+            (Some(value_name), Value::Array(pair)) => {
+                data_ctx.set_scope_with_value(item_name, pair[0].clone());
+                data_ctx.set_scope_with_value(value_name, pair[1].clone());
+                let value_origin = array_path.zip(pair[0].as_str());
+                data_ctx.set_scope_origin(
+                    value_name,
+                    value_origin.map(|(path, key)| format!("{}.{}", path, key)),
+                );
+            }
+            _ => {
+                data_ctx.set_scope_with_value(item_name, item.clone());
+                data_ctx.set_scope_origin(item_name, item_origin.clone());
+            }
+        }
+        if let Some(source_map) = &data_ctx.source_map {
+            source_map.borrow_mut().iterations.push(i);
+        }
+
+        let replaced = fill(template_bytes, sub_ast, data_ctx, true, false);
+        if let Some(source_map) = &data_ctx.source_map {
+            source_map.borrow_mut().iterations.pop();
+        }
+        filled.push(replaced);
+
+        if let Some(join_with) = join_with.as_ref() {
+            if i < array.len() - 1 {
+                filled.push_str(join_with);
+            }
+        }
+        data_ctx.pop_scope();
+    }
+    filled
+}
+
+/// Re-enter the innermost recursive loop with the children, like 'loop(node.children)'
+fn fill_recursive_loop(
+    data_ctx: &mut AutoDataContext,
+    args: &str,
+    line_indent: Option<&str>,
+) -> Filled {
+    let Some(recursive_loop) = data_ctx.recursive_loops.last().cloned() else {
+        return data_ctx
+            .fail("loop() is only available in recursive for".to_owned())
+            .into();
+    };
+    let depth = recursive_loop.depth + 1;
+    if depth > data_ctx.options.recursion_limit {
+        return data_ctx
+            .fail(format!(
+                "Recursion limit exceeded: {}",
+                data_ctx.options.recursion_limit
+            ))
+            .into();
+    }
+    let children_path = data_ctx.expression_path(args.trim());
+    let children = match get_expression_value(data_ctx, args.trim()) {
+        Value::Array(children) => children,
+        Value::Null => Vec::new(),
+        _ => {
+            return data_ctx
+                .fail(format!("Recursive loop children must be array: {}", args))
+                .into()
+        }
+    };
+    if let (Some(report), Some(stat_index)) = (&data_ctx.report, recursive_loop.stat_index) {
+        report
+            .borrow_mut()
+            .record_loop_run(stat_index, children.len());
+    }
+    // The parent item is hidden from the children, so the key which a child does not have
+    // is not found in its parent, like 'node.children' of a leaf node
+    let parent_item = data_ctx.take_scope_value(&recursive_loop.item_name);
+    let replaced = fill_call(data_ctx, line_indent, |data_ctx| {
+        data_ctx.set_scope_with_string("$depth", depth.to_string());
+        data_ctx.recursive_loops.push(RecursiveLoop {
+            depth,
+            ..recursive_loop.clone()
+        });
+        let replaced = fill_for(
+            recursive_loop.source.as_bytes(),
+            &recursive_loop.sub_ast,
+            data_ctx,
+            &recursive_loop.item_name,
+            None,
+            &children,
+            children_path.as_deref(),
+        );
+        data_ctx.recursive_loops.pop();
+        replaced
+    });
+    if let Some(parent_item) = parent_item {
+        data_ctx.restore_scope_value(&recursive_loop.item_name, parent_item);
+    }
+    replaced
+}
+
+/// Fill the content of a call in placeholder (for example 'super()') with the line indent as its tag indent,
+/// and its first line is not indented because the line indent is filled
+fn fill_call(
+    data_ctx: &mut AutoDataContext,
+    line_indent: Option<&str>,
+    fill_content: impl FnOnce(&mut AutoDataContext) -> Filled,
+) -> Filled {
+    data_ctx.push_scope();
+    data_ctx.set_scope_with_string("tag_inline", false.to_string());
+    if let Some(line_indent) = line_indent {
+        data_ctx.set_scope_with_string("tag_indent", line_indent.to_owned());
+    }
+    let replaced = fill_content(data_ctx);
+    data_ctx.pop_scope();
+    match line_indent {
+        Some(line_indent) if replaced.text.starts_with(line_indent) => {
+            replaced.edit([(0..line_indent.len(), String::new())])
+        }
+        _ => replaced,
+    }
+}
+
+/// Fill the parent's define of the block which is being filled
+fn fill_super(data_ctx: &mut AutoDataContext, line_indent: Option<&str>) -> Filled {
+    let Some((name, level)) = data_ctx.block_stack.last().cloned() else {
+        return data_ctx
+            .fail("super() is only available in block".to_owned())
+            .into();
+    };
+    fill_call(data_ctx, line_indent, |data_ctx| {
+        fill_block(data_ctx, &name, level + 1)
+            .unwrap_or_else(|| data_ctx.fail(format!("No parent block: {}", name)).into())
+    })
+}
+
+/// Fill the macro with the arguments in its own scope, arguments are like '"id", type, nullable=false'
+/// @param name Name of the macro which is called, like 'alias.name' if it is imported with alias
+fn fill_macro(
+    data_ctx: &mut AutoDataContext,
+    name: &str,
+    macro_define: &MacroDefine,
+    args: &str,
+    line_indent: Option<&str>,
+) -> Filled {
+    let depth = data_ctx.macro_depth + 1;
+    if depth > data_ctx.options.recursion_limit {
+        return data_ctx
+            .fail(format!(
+                "Recursion limit exceeded: {}",
+                data_ctx.options.recursion_limit
+            ))
+            .into();
+    }
+    // (value, data path of the value) of the arguments
+    let argument = |expression: &str| {
+        (
+            get_expression_value(data_ctx, expression),
+            data_ctx.expression_path(expression),
+        )
+    };
+    let mut positional_args = Vec::new();
+    let mut keyword_args = HashMap::new();
+    for arg in split_outside_quotes(args, ',').into_iter().map(str::trim) {
+        if arg.is_empty() {
+            continue;
+        }
+        match arg.split_once('=') {
+            Some((key, value)) if !arg.starts_with('"') => {
+                keyword_args.insert(key.trim(), argument(value.trim()));
+            }
+            _ => positional_args.push(argument(arg)),
+        }
+    }
+    if positional_args.len() > macro_define.params.len() {
+        return data_ctx
+            .fail(format!("Too many macro arguments: {}", args))
+            .into();
+    }
+    let mut positional_args = positional_args.into_iter();
+    let mut param_values = Vec::with_capacity(macro_define.params.len());
+    for param in &macro_define.params {
+        let value = match (
+            positional_args.next(),
+            keyword_args.remove(param.name.as_str()),
+        ) {
+            (Some(_), Some(_)) => {
+                return data_ctx
+                    .fail(format!("Macro argument is duplicated: {}", param.name))
+                    .into()
+            }
+            (Some(value), None) | (None, Some(value)) => value,
+            (None, None) => match &param.default {
+                Some((_, default)) => argument(default),
+                None => (Value::Null, None),
+            },
+        };
+        param_values.push((param.name.as_str(), value));
+    }
+    if let Some(key) = keyword_args.keys().next() {
+        return data_ctx
+            .fail(format!("Unknown macro argument: {}", key))
+            .into();
+    }
+    // The macro which is imported with alias calls the other macros of its template by their own names
+    let outer_macros = name.split_once('.').map(|(alias, _)| {
+        let prefix = format!("{}.", alias);
+        let library: Vec<_> = data_ctx
+            .macros
+            .iter()
+            .filter_map(|(name, define)| {
+                Some((name.strip_prefix(&prefix)?.to_owned(), Rc::clone(define)))
+            })
+            .collect();
+        let outer_macros = data_ctx.macros.clone();
+        data_ctx.macros.extend(library);
+        outer_macros
+    });
+    data_ctx.macro_depth = depth;
+    let filled = fill_call(data_ctx, line_indent, |data_ctx| {
+        for (name, (value, origin)) in param_values {
+            data_ctx.set_scope_with_value(name, value);
+            data_ctx.set_scope_origin(name, origin);
+        }
+        fill(
+            macro_define.source.as_bytes(),
+            &macro_define.sub_ast,
+            data_ctx,
+            true,
+            true,
+        )
+    });
+    data_ctx.macro_depth = depth - 1;
+    if let Some(outer_macros) = outer_macros {
+        data_ctx.macros = outer_macros;
+    }
+    filled
+}
+
 fn get_tag_indent(
     template_bytes: &[u8],
     token_index: usize,
@@ -1040,9 +3828,12 @@ fn get_tag_indent(
     min_indent_len_in_tag: usize,
 ) -> Option<String> {
     // First in row or first item in tag will be fill indent
-    if token_ctx.first_in_line || token_ctx.in_tag && token_index == 0 {
+    let is_first = token_ctx.first_in_line || token_ctx.in_tag && token_index == 0;
+    // The first line of tag which is not first in row is not indented
+    data_ctx.set_scope_with_string("tag_inline", (!is_first).to_string());
+    if is_first {
         if token_ctx.in_tag {
-            let indent = get_indent_in_tag(template_bytes, &data_ctx, line, min_indent_len_in_tag);
+            let indent = get_indent_in_tag(template_bytes, data_ctx, line, min_indent_len_in_tag);
             if let Some(indent) = indent {
                 if !indent.is_empty() {
                     data_ctx.set_scope_with_string("tag_indent", indent);
@@ -1059,14 +3850,12 @@ fn get_tag_indent(
     None
 }
 
-fn unicode_escape(v: &str) -> Option<String> {
+/// @return None if the escape is invalid, and it is recorded as a warning in the recovery mode
+fn unicode_escape(data_ctx: &AutoDataContext, v: &str) -> Option<String> {
     match unicode_escape::decode(v) {
         Ok(decoded_v) => Some(decoded_v),
         Err(e) => {
-            // Debug
-            if cfg!(debug_assertions) && cfg!(not(test)) {
-                println!("[warn] Unicode escape error: {}", e)
-            }
+            data_ctx.warn(format!("Unicode escape error: {}, {}", v, e));
             None
         }
     }
@@ -1092,10 +3881,11 @@ fn get_indent_in_tag(
             let tag_indent = data_ctx.get_string("tag_indent"); // todo 检查到这里
             if let Some(mut raw_indent) = raw_indent {
                 if min_indent_len_in_tag > 0 {
-                    raw_indent = raw_indent
-                        .get(min_indent_len_in_tag..)
-                        .map(|o| o.to_owned())
-                        .unwrap_or(String::new());
+                    raw_indent = strip_indent_columns(
+                        &raw_indent,
+                        min_indent_len_in_tag,
+                        data_ctx.options.tab_width,
+                    );
                 }
                 if let Some(tag_indent) = tag_indent {
                     Some(tag_indent + &raw_indent)
@@ -1109,6 +3899,30 @@ fn get_indent_in_tag(
     }
 }
 
+/// Display width of the indent which is start at `start_column`,
+/// tab is aligned to the next tab stop and full-width space (U+3000) is 2 columns
+fn indent_columns(start_column: usize, indent: &str, tab_width: usize) -> usize {
+    indent.chars().fold(start_column, |column, c| match c {
+        '\t' if tab_width > 0 => column + tab_width - column % tab_width,
+        '\t' => column,
+        '\u{3000}' => column + 2,
+        _ => column + 1,
+    })
+}
+
+/// Remove the indent by display width,
+/// and the rest columns of a character across the boundary (for example tab) are filled by spaces
+fn strip_indent_columns(indent: &str, columns: usize, tab_width: usize) -> String {
+    let mut column = 0;
+    for (idx, c) in indent.char_indices() {
+        if column >= columns {
+            return " ".repeat(column - columns) + &indent[idx..];
+        }
+        column = indent_columns(column, c.encode_utf8(&mut [0; 4]), tab_width);
+    }
+    " ".repeat(column.saturating_sub(columns))
+}
+
 fn bytes_to_str(bytes: &[u8], start: usize, end: usize) -> &str {
     str::from_utf8(&bytes[start..end]).expect("Convert &[u8] to &str fail")
 }
@@ -1119,31 +3933,77 @@ fn get_expression_result(
     expression_name: &str,
 ) -> Option<String> {
     match expression_type {
-        ExpressionType::VariableName => data_ctx.get_string(&expression_name),
-        ExpressionType::String => unicode_escape(&expression_name[1..expression_name.len() - 1]),
+        ExpressionType::VariableName => data_ctx.get_string(expression_name),
+        ExpressionType::String => {
+            unicode_escape(data_ctx, &expression_name[1..expression_name.len() - 1])
+        }
         ExpressionType::Number | ExpressionType::Boolean => Some(expression_name.to_owned()),
     }
 }
 
-/// @return (env_key, env_value)
+/// Value of the expression keeps its type, and the undefined variable is null
+fn get_expression_value(data_ctx: &AutoDataContext, expression: &str) -> Value {
+    match try_assess_expression(expression) {
+        Some(expression_type) => {
+            get_typed_expression_result(data_ctx, &expression_type, expression)
+        }
+        None => {
+            data_ctx.fail(format!("Unvalid variable name: {}", expression));
+            Value::Null
+        }
+    }
+}
+
+fn get_typed_expression_result(
+    data_ctx: &AutoDataContext,
+    expression_type: &ExpressionType,
+    expression_name: &str,
+) -> Value {
+    match expression_type {
+        ExpressionType::VariableName => data_ctx.get_value(expression_name).unwrap_or(Value::Null),
+        ExpressionType::String => Value::String(
+            get_expression_result(data_ctx, expression_type, expression_name).unwrap_or_default(),
+        ),
+        ExpressionType::Number | ExpressionType::Boolean => {
+            serde_json::from_str(expression_name).unwrap_or(Value::Null)
+        }
+    }
+}
+
+/// Typed comparison, and the numbers are compared by value, for example 1 == 1.0
+fn is_value_equal(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(left), Value::Number(right)) => left.as_f64() == right.as_f64(),
+        _ => left == right,
+    }
+}
+
+/// @return (env_key, env_value), None if the key is not a variable name in the recovery mode
 fn get_kv_from_env_define<'a>(
     template_bytes: &'a [u8],
     start: usize,
     end: usize,
-    data_ctx: &'a AutoDataContext<'a>,
-) -> (&'a str, String) {
+    data_ctx: &AutoDataContext,
+) -> Option<(&'a str, String)> {
     let (k, v) = bytes_to_str(template_bytes, start, end)
         .split_once("=")
         .unwrap();
     let k = k.trim();
-    let k_type = assess_expression(k);
-    if k_type != ExpressionType::VariableName {
-        panic!("Token::Env define error: {}", k);
+    if try_assess_expression(k) != Some(ExpressionType::VariableName) {
+        data_ctx.fail(format!("Token::Env define error: {}", k));
+        return None;
     }
     let v = v.trim();
-    let v_type = assess_expression(v);
-    let v = get_expression_result(data_ctx, &v_type, v).unwrap_or_default();
-    (k, v)
+    // Unquoted text which is not a valid expression or an undefined variable is used as it is,
+    // for example '{$ join_with = , $}'
+    let v = match try_assess_expression(v) {
+        Some(ExpressionType::VariableName) => {
+            data_ctx.get_string(v).unwrap_or_else(|| v.to_owned())
+        }
+        Some(v_type) => get_expression_result(data_ctx, &v_type, v).unwrap_or_default(),
+        None => v.to_owned(),
+    };
+    Some((k, v))
 }
 
 fn normalize_spaces(text: &str) -> String {
@@ -1162,3 +4022,77 @@ fn normalize_spaces(text: &str) -> String {
     }
     result.trim().to_string()
 }
+
+/// Apply the options `line_ending` and `trailing_newline` to the filled result
+fn normalize_line_feed(template_bytes: &[u8], filled: Filled, options: &FillOptions) -> Filled {
+    let line_feed = match options.line_ending {
+        LineEnding::Lf => "\n",
+        LineEnding::Crlf => "\r\n",
+        LineEnding::Keep | LineEnding::Dominant => {
+            let crlf_cnt = template_bytes.windows(2).filter(|w| w == b"\r\n").count();
+            let lf_cnt = template_bytes.iter().filter(|b| **b == b'\n').count() - crlf_cnt;
+            if crlf_cnt > lf_cnt {
+                "\r\n"
+            } else {
+                "\n"
+            }
+        }
+    };
+    let mut filled = if options.line_ending == LineEnding::Keep {
+        filled
+    } else {
+        let edits: Vec<_> = filled
+            .text
+            .match_indices('\n')
+            .map(|(idx, _)| {
+                let start = if filled.text[..idx].ends_with('\r') {
+                    idx - 1
+                } else {
+                    idx
+                };
+                start..idx + 1
+            })
+            .filter(|range| filled.text[range.clone()] != *line_feed)
+            .map(|range| (range, line_feed.to_owned()))
+            .collect();
+        filled.edit(edits)
+    };
+    match options.trailing_newline {
+        TrailingNewline::Keep => {}
+        TrailingNewline::Ensure => {
+            if !filled.text.is_empty() && !filled.text.ends_with('\n') {
+                filled.push_str(line_feed);
+            }
+        }
+        TrailingNewline::Strip => {
+            let len = filled.text.trim_end_matches(['\r', '\n']).len();
+            filled.truncate(len);
+        }
+    }
+    filled
+}
+
+/// Apply the option `indent_style` to the indent of each line in the filled result
+fn convert_indent(filled: Filled, options: &FillOptions) -> Filled {
+    if options.indent_style == IndentStyle::Keep {
+        return filled;
+    }
+    let tab_width = options.tab_width.max(1);
+    let mut line_start = 0;
+    let mut edits = Vec::new();
+    for line in filled.text.split_inclusive('\n') {
+        let content =
+            line.trim_start_matches(|c: char| c != '\r' && c != '\n' && c.is_whitespace());
+        let indent_len = line.len() - content.len();
+        let columns = indent_columns(0, &line[..indent_len], tab_width);
+        let (levels, rest) = (columns / tab_width, columns % tab_width);
+        let indent = match options.indent_style {
+            IndentStyle::Spaces(width) => " ".repeat(levels * width + rest),
+            IndentStyle::Tabs => format!("{}{}", "\t".repeat(levels), " ".repeat(rest)),
+            IndentStyle::Keep => unreachable!(),
+        };
+        edits.push((line_start..line_start + indent_len, indent));
+        line_start += line.len();
+    }
+    filled.edit(edits)
+}
@@ -1,4 +1,34 @@
+pub mod ast;
+mod check;
+mod error;
 mod fill;
+mod loader;
+mod options;
+mod report;
+mod source_map;
+mod syntax;
+mod tag;
+mod template;
 mod tpd;
+mod vars;
 
+pub use check::{check, Diagnostic, Severity};
+pub use error::FillError;
 pub use fill::fill_template as fill;
+pub use fill::fill_template_files as fill_files;
+pub use fill::fill_template_with_diagnostics as fill_with_diagnostics;
+pub use fill::fill_template_with_options as fill_with_options;
+pub use fill::parse_template as parse;
+pub use fill::try_fill_template_files as try_fill_files;
+pub use fill::try_fill_template_with_mode as try_fill_with_mode;
+pub use fill::try_fill_template_with_options as try_fill;
+pub use fill::TagContext;
+pub use fill::{RenderMode, RenderOutput};
+pub use loader::{FileSystemLoader, MemoryLoader, TemplateLoader};
+pub use options::{FillOptions, IndentStyle, LineEnding, TrailingNewline};
+pub use report::{LoopStat, MissingPlaceholder, RenderReport};
+pub use source_map::{SourceMap, SourceMapping};
+pub use syntax::Syntax;
+pub use tag::CustomTag;
+pub use template::Template;
+pub use vars::{variables, variables_schema, variables_skeleton};
@@ -0,0 +1,78 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    fs,
+    io::{self, ErrorKind},
+    path::{Component, Path, PathBuf},
+};
+
+/// Load the template content by name, it is used by the tag 'include'
+pub trait TemplateLoader: Debug {
+    fn load(&self, name: &str) -> io::Result<String>;
+}
+
+/// Load the template from the directory, the template outside the directory is forbidden
+#[derive(Debug, Clone)]
+pub struct FileSystemLoader {
+    root: PathBuf,
+}
+
+impl FileSystemLoader {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl TemplateLoader for FileSystemLoader {
+    fn load(&self, name: &str) -> io::Result<String> {
+        let name_path = Path::new(name);
+        if name_path
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(io::Error::new(
+                ErrorKind::PermissionDenied,
+                format!(
+                    "Template path must be relative to the template root: {}",
+                    name
+                ),
+            ));
+        }
+        // Symbolic link may point to the outside of root
+        let root = self.root.canonicalize()?;
+        let path = root.join(name_path).canonicalize()?;
+        if !path.starts_with(&root) {
+            return Err(io::Error::new(
+                ErrorKind::PermissionDenied,
+                format!("Template is outside the template root: {}", name),
+            ));
+        }
+        fs::read_to_string(path)
+    }
+}
+
+/// Load the template from memory
+#[derive(Debug, Clone, Default)]
+pub struct MemoryLoader {
+    templates: HashMap<String, String>,
+}
+
+impl MemoryLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert<K: Into<String>, V: Into<String>>(&mut self, name: K, content: V) {
+        self.templates.insert(name.into(), content.into());
+    }
+}
+
+impl TemplateLoader for MemoryLoader {
+    fn load(&self, name: &str) -> io::Result<String> {
+        self.templates.get(name).cloned().ok_or_else(|| {
+            io::Error::new(ErrorKind::NotFound, format!("Template not found: {}", name))
+        })
+    }
+}
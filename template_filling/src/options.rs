@@ -0,0 +1,136 @@
+use std::{rc::Rc, str::FromStr};
+
+use crate::{loader::TemplateLoader, syntax::Syntax, tag::CustomTag};
+
+/// Render-wide options of template filling
+#[derive(Debug, Clone)]
+pub struct FillOptions {
+    /// Remove the first line feed after a logical tag, like `{% ... %}`,
+    /// the tag which is alone in its line is not trimmed as its line is dropped already
+    pub trim_blocks: bool,
+    /// Strip the whitespaces from the start of a line to a logical tag, like `{% ... %}`
+    pub lstrip_blocks: bool,
+    /// Delimiters of the symbols, it is overwritten by the syntax directive at the first line of template
+    pub syntax: Syntax,
+    /// Fill the escaped symbol head `\{{` as text `{{`, and `\\{{` as `\` followed by the symbol.
+    /// It is off by default, so the backslash before a symbol is text, like `C:\{{ dir }}`
+    pub escape_symbols: bool,
+    /// Line feed style of the filled result
+    pub line_ending: LineEnding,
+    /// Line feed at the end of the filled result
+    pub trailing_newline: TrailingNewline,
+    /// Indent the continuation lines of a multi-line placeholder value
+    /// to the indent of the line where the placeholder is
+    pub auto_indent: bool,
+    /// Display width of a tab when measuring the indent, default is 4
+    pub tab_width: usize,
+    /// Indent style of the filled result
+    pub indent_style: IndentStyle,
+    /// Load the template by name for the tags 'include', 'extends' and 'import'
+    pub loader: Option<Rc<dyn TemplateLoader>>,
+    /// Maximum depth of the recursive loop, the macro calls and the filter 'render', default is 64
+    pub recursion_limit: usize,
+    /// Tags registered by the application, a built-in tag can not be overridden
+    pub custom_tags: Vec<Rc<dyn CustomTag>>,
+}
+
+impl Default for FillOptions {
+    fn default() -> Self {
+        Self {
+            trim_blocks: false,
+            lstrip_blocks: false,
+            syntax: Syntax::default(),
+            escape_symbols: false,
+            line_ending: LineEnding::default(),
+            trailing_newline: TrailingNewline::default(),
+            auto_indent: false,
+            tab_width: 4,
+            indent_style: IndentStyle::default(),
+            loader: None,
+            recursion_limit: 64,
+            custom_tags: Vec::new(),
+        }
+    }
+}
+
+/// Line feed style of the filled result
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LineEnding {
+    /// Keep the line feeds of template and data as they are
+    #[default]
+    Keep,
+    /// All line feeds are `\n`
+    Lf,
+    /// All line feeds are `\r\n`
+    Crlf,
+    /// All line feeds follow the most used line feed of template, `\n` if they are the same
+    Dominant,
+}
+
+impl FromStr for LineEnding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(LineEnding::Keep),
+            "lf" => Ok(LineEnding::Lf),
+            "crlf" => Ok(LineEnding::Crlf),
+            "dominant" => Ok(LineEnding::Dominant),
+            _ => Err(format!("Unsupported line ending: {}", s)),
+        }
+    }
+}
+
+/// Line feed at the end of the filled result
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TrailingNewline {
+    /// Keep the end of filled result as it is
+    #[default]
+    Keep,
+    /// Append a line feed if the filled result is not end with it
+    Ensure,
+    /// Remove all line feeds at the end of filled result
+    Strip,
+}
+
+impl FromStr for TrailingNewline {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(TrailingNewline::Keep),
+            "ensure" => Ok(TrailingNewline::Ensure),
+            "strip" => Ok(TrailingNewline::Strip),
+            _ => Err(format!("Unsupported trailing newline: {}", s)),
+        }
+    }
+}
+
+/// Indent style of the filled result, an indent level is `tab_width` columns
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum IndentStyle {
+    /// Keep the indent as it is
+    #[default]
+    Keep,
+    /// Each indent level is converted to the number of spaces
+    Spaces(usize),
+    /// Each indent level is converted to a tab
+    Tabs,
+}
+
+/// Parse the indent style, like 'keep', 'tabs' or 'spaces:2'
+impl FromStr for IndentStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "keep" => Ok(IndentStyle::Keep),
+            None if s == "tabs" => Ok(IndentStyle::Tabs),
+            Some(("spaces", width)) => width
+                .parse()
+                .map(IndentStyle::Spaces)
+                .map_err(|_| format!("Illegal indent width: {}", width)),
+            _ => Err(format!("Unsupported indent style: {}", s)),
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display},
+};
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Usage of the data and the statistics of a filling
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RenderReport {
+    /// Placeholders which are filled as not found, in the order of their first filling
+    pub missing_placeholders: Vec<MissingPlaceholder>,
+    /// Paths of the data keys which are never read, like `table.columns[].comment`
+    pub unused_keys: Vec<String>,
+    /// Loops in the order of their first running
+    pub loops: Vec<LoopStat>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MissingPlaceholder {
+    /// Expression of the placeholder, like `column.comment | indent`
+    pub placeholder: String,
+    /// Times it is filled
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LoopStat {
+    /// Head of the loop, like `column in table.columns`
+    pub expression: String,
+    /// Name of the included template where the loop is, None if it is in the filled template
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Line of the loop tag, start at 1
+    pub line: usize,
+    /// Column of the loop tag in chars, start at 1
+    pub column: usize,
+    /// Times the loop is filled, a loop in another loop runs for each item of the outer loop
+    pub runs: usize,
+    /// Times its body is filled in all runs
+    pub iterations: usize,
+}
+
+impl Display for RenderReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Missing placeholders:")?;
+        if self.missing_placeholders.is_empty() {
+            writeln!(f, "  (none)")?;
+        }
+        for missing in &self.missing_placeholders {
+            writeln!(f, "  {{{{ {} }}}} x{}", missing.placeholder, missing.count)?;
+        }
+        writeln!(f, "Unused data keys:")?;
+        if self.unused_keys.is_empty() {
+            writeln!(f, "  (none)")?;
+        }
+        for key in &self.unused_keys {
+            writeln!(f, "  {}", key)?;
+        }
+        write!(f, "Loops:")?;
+        if self.loops.is_empty() {
+            write!(f, "\n  (none)")?;
+        }
+        for stat in &self.loops {
+            write!(f, "\n  {} at ", stat.expression)?;
+            if let Some(template) = &stat.template {
+                write!(f, "{}:", template)?;
+            }
+            write!(
+                f,
+                "{}:{}, {} runs, {} iterations",
+                stat.line, stat.column, stat.runs, stat.iterations
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Usage which is recorded while filling, it is reported by `finish`
+#[derive(Debug, Default)]
+pub(crate) struct ReportState {
+    /// Data paths which are read, like `table.columns[].name`
+    used_paths: HashSet<String>,
+    /// Data paths of the arrays which are iterated, their items are not read as a whole
+    iterated_paths: HashSet<String>,
+    missing_placeholders: Vec<MissingPlaceholder>,
+    loops: Vec<LoopStat>,
+    /// Index of the loop stat by (template name, start of the loop tag)
+    loop_indexes: HashMap<(Option<String>, usize), usize>,
+}
+
+impl ReportState {
+    pub fn mark_used(&mut self, path: String) {
+        self.used_paths.insert(path);
+    }
+
+    pub fn mark_iterated(&mut self, path: String) {
+        self.iterated_paths.insert(path);
+    }
+
+    pub fn record_missing(&mut self, placeholder: &str) {
+        match self
+            .missing_placeholders
+            .iter_mut()
+            .find(|missing| missing.placeholder == placeholder)
+        {
+            Some(missing) => missing.count += 1,
+            None => self.missing_placeholders.push(MissingPlaceholder {
+                placeholder: placeholder.to_owned(),
+                count: 1,
+            }),
+        }
+    }
+
+    /// Index of the loop which is identified by the template and the start of its tag,
+    /// the location is computed when it runs first
+    pub fn loop_index(
+        &mut self,
+        template: Option<String>,
+        start: usize,
+        new_stat: impl FnOnce() -> LoopStat,
+    ) -> usize {
+        let loops = &mut self.loops;
+        *self
+            .loop_indexes
+            .entry((template, start))
+            .or_insert_with(|| {
+                loops.push(new_stat());
+                loops.len() - 1
+            })
+    }
+
+    pub fn record_loop_run(&mut self, index: usize, iterations: usize) {
+        let stat = &mut self.loops[index];
+        stat.runs += 1;
+        stat.iterations += iterations;
+    }
+
+    pub fn finish(self, data: &Value) -> RenderReport {
+        let mut leaf_paths = Vec::new();
+        collect_leaf_paths(data, String::new(), &mut leaf_paths);
+        let unused_keys = leaf_paths
+            .into_iter()
+            .filter(|path| !self.is_used(path))
+            .collect();
+        RenderReport {
+            missing_placeholders: self.missing_placeholders,
+            unused_keys,
+            loops: self.loops,
+        }
+    }
+
+    /// The key is used if it or its ancestor (which is read as a whole) is read,
+    /// or it is an iterated array which is empty
+    fn is_used(&self, path: &str) -> bool {
+        if self.iterated_paths.contains(path) {
+            return true;
+        }
+        let mut prefix = path;
+        loop {
+            if self.used_paths.contains(prefix) {
+                return true;
+            }
+            match prefix.rfind(['.', '[']) {
+                Some(idx) => prefix = &prefix[..idx],
+                None => return false,
+            }
+        }
+    }
+}
+
+/// Paths of the values which are not objects or non-empty arrays,
+/// and the items of array are merged like `columns[].name`
+fn collect_leaf_paths(value: &Value, path: String, leaf_paths: &mut Vec<String>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                let path = if path.is_empty() {
+                    key.to_owned()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                collect_leaf_paths(value, path, leaf_paths);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for item in items {
+                collect_leaf_paths(item, format!("{}[]", path), leaf_paths);
+            }
+        }
+        _ => {
+            if !path.is_empty() && !leaf_paths.contains(&path) {
+                leaf_paths.push(path);
+            }
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use std::ops::Range;
+
+use serde::Serialize;
+
+use crate::ast::Span;
+
+/// Origins of the byte ranges of the filled output
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SourceMap {
+    /// Mappings in the order of output, the ranges are not overlapped
+    pub mappings: Vec<SourceMapping>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SourceMapping {
+    /// Byte range in the output
+    pub output: Span,
+    /// Name of the included template which produces the range, None if it is the filled template
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Line of the token which produces the range, start at 1
+    pub line: usize,
+    /// Column of the token in chars, start at 1
+    pub column: usize,
+    /// Index of the item in each loop which encloses the token, the outermost loop is the first
+    pub iterations: Vec<usize>,
+}
+
+impl SourceMap {
+    /// Mapping of the byte in the output
+    pub fn origin(&self, output_idx: usize) -> Option<&SourceMapping> {
+        let idx = self
+            .mappings
+            .partition_point(|mapping| mapping.output.start <= output_idx);
+        self.mappings[..idx]
+            .last()
+            .filter(|mapping| output_idx < mapping.output.end)
+    }
+
+    /// Mapping of each line in the output, it is the origin of the first non-whitespace character,
+    /// and the blank line has no origin
+    pub fn line_origins(&self, output: &str) -> Vec<Option<&SourceMapping>> {
+        let mut line_start = 0;
+        output
+            .split_inclusive('\n')
+            .map(|line| {
+                let origin = line
+                    .find(|c: char| !c.is_whitespace())
+                    .and_then(|idx| self.origin(line_start + idx));
+                line_start += line.len();
+                origin
+            })
+            .collect()
+    }
+
+    /// Prefix each line of the output with its origin, like `ddl.tmpl:4[1] | id INT,`
+    /// @param template_name Name of the filled template
+    pub fn annotate(&self, output: &str, template_name: &str) -> String {
+        let prefixes: Vec<String> = self
+            .line_origins(output)
+            .into_iter()
+            .map(|origin| match origin {
+                Some(mapping) => {
+                    let mut prefix = format!(
+                        "{}:{}",
+                        mapping.template.as_deref().unwrap_or(template_name),
+                        mapping.line
+                    );
+                    if !mapping.iterations.is_empty() {
+                        let iterations: Vec<String> =
+                            mapping.iterations.iter().map(usize::to_string).collect();
+                        prefix.push_str(&format!("[{}]", iterations.join(",")));
+                    }
+                    prefix
+                }
+                None => String::new(),
+            })
+            .collect();
+        let width = prefixes
+            .iter()
+            .map(|prefix| prefix.chars().count())
+            .max()
+            .unwrap_or(0);
+        output
+            .split_inclusive('\n')
+            .zip(prefixes)
+            .map(|(line, prefix)| format!("{:<width$} | {}", prefix, line, width = width))
+            .collect()
+    }
+}
+
+/// Origin of a filled token, it is referred by the offset where its content starts
+#[derive(Debug, Clone)]
+pub(crate) struct TokenOrigin {
+    pub template: Option<String>,
+    pub line: usize,
+    pub column: usize,
+    pub iterations: Vec<usize>,
+}
+
+/// Origins which are recorded while filling, they are mapped to the output by `extract`
+#[derive(Debug, Default)]
+pub(crate) struct SourceMapState {
+    origins: Vec<TokenOrigin>,
+    /// Index of the item in each loop which is being filled
+    pub iterations: Vec<usize>,
+}
+
+impl SourceMapState {
+    /// @return Index of the origin, it is marked in the filled content by `Filled::mark`
+    pub fn record(&mut self, origin: TokenOrigin) -> usize {
+        self.origins.push(origin);
+        self.origins.len() - 1
+    }
+
+    /// Map the ranges between the marked offsets of the filled content to their origins
+    pub fn extract(&self, filled: &Filled) -> SourceMap {
+        // The token which fills nothing before the next token is dropped
+        let mut starts: Vec<(usize, usize)> = Vec::with_capacity(filled.origins.len());
+        for &(start, origin_idx) in &filled.origins {
+            if starts.last().is_some_and(|(last, _)| *last == start) {
+                starts.pop();
+            }
+            starts.push((start, origin_idx));
+        }
+        let ends = starts
+            .iter()
+            .skip(1)
+            .map(|(start, _)| *start)
+            .chain([filled.text.len()]);
+        let mappings = starts
+            .iter()
+            .zip(ends)
+            .filter(|((start, _), end)| start < end)
+            .map(|((start, origin_idx), end)| {
+                let origin = self.origins[*origin_idx].clone();
+                SourceMapping {
+                    output: Span::new(*start, end),
+                    template: origin.template,
+                    line: origin.line,
+                    column: origin.column,
+                    iterations: origin.iterations,
+                }
+            })
+            .collect();
+        SourceMap { mappings }
+    }
+}
+
+/// Filled content and the offsets where the contents of tokens start, the offsets are kept out of
+/// the text so the filled data is never rewritten, and they are only marked if the source map is generated
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Filled {
+    pub text: String,
+    /// (offset in text, index of origin) in the order of offset
+    origins: Vec<(usize, usize)>,
+}
+
+impl From<String> for Filled {
+    fn from(text: String) -> Self {
+        Self {
+            text,
+            origins: Vec::new(),
+        }
+    }
+}
+
+impl Filled {
+    pub fn len(&self) -> usize {
+        self.text.len()
+    }
+
+    /// Mark the content filled after now comes from the origin
+    pub fn mark(&mut self, origin_idx: usize) {
+        self.origins.push((self.text.len(), origin_idx));
+    }
+
+    pub fn push_str(&mut self, text: &str) {
+        self.text.push_str(text);
+    }
+
+    /// Append the filled content with its origins
+    pub fn push(&mut self, filled: Filled) {
+        let base = self.text.len();
+        self.text.push_str(&filled.text);
+        self.origins.extend(
+            filled
+                .origins
+                .into_iter()
+                .map(|(offset, origin_idx)| (base + offset, origin_idx)),
+        );
+    }
+
+    /// The origins marked at or after the length are dropped with the text
+    pub fn truncate(&mut self, len: usize) {
+        self.text.truncate(len);
+        self.origins.retain(|(offset, _)| *offset < len);
+    }
+
+    /// Replace the byte ranges (in order, not overlapped) with the texts, and move the origins with the text.
+    /// The origin in a replaced range is moved to the end of its replacement
+    pub fn edit(self, edits: impl IntoIterator<Item = (Range<usize>, String)>) -> Filled {
+        let mut text = String::with_capacity(self.text.len());
+        let mut origins = Vec::with_capacity(self.origins.len());
+        let mut rest_origins = self.origins.into_iter().peekable();
+        let mut last = 0;
+        for (range, replacement) in edits {
+            while let Some((offset, origin_idx)) =
+                rest_origins.next_if(|(offset, _)| *offset <= range.start)
+            {
+                origins.push((text.len() + offset - last, origin_idx));
+            }
+            text.push_str(&self.text[last..range.start]);
+            text.push_str(&replacement);
+            while let Some((_, origin_idx)) =
+                rest_origins.next_if(|(offset, _)| *offset <= range.end)
+            {
+                origins.push((text.len(), origin_idx));
+            }
+            last = range.end;
+        }
+        origins.extend(
+            rest_origins.map(|(offset, origin_idx)| (text.len() + offset - last, origin_idx)),
+        );
+        text.push_str(&self.text[last..]);
+        Filled { text, origins }
+    }
+}
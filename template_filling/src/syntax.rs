@@ -0,0 +1,161 @@
+use std::str::FromStr;
+
+/// The template can select its syntax at the first line, for example
+/// ```text
+/// #!syntax bracket
+/// #!syntax default placeholder=<< >>
+/// ```
+pub const SYNTAX_DIRECTIVE: &str = "#!syntax";
+
+/// Delimiters (head, tail) of the template symbols
+#[derive(Debug, Clone, PartialEq)]
+pub struct Syntax {
+    /// Logical tag, default is `{% %}`
+    pub logical: (String, String),
+    /// Placeholder, default is `{{ }}`
+    pub placeholder: (String, String),
+    /// Env define, default is `{$ $}`
+    pub env: (String, String),
+    /// Raw text, default is `{# #}`
+    pub raw: (String, String),
+    /// Comment which renders nothing, default is `{%# #%}`.
+    /// A logical tag never starts with `#`, so it does not collide with the logical tag or the raw text
+    pub comment: (String, String),
+}
+
+impl Default for Syntax {
+    fn default() -> Self {
+        Self::new(
+            ("{%", "%}"),
+            ("{{", "}}"),
+            ("{$", "$}"),
+            ("{#", "#}"),
+            ("{%#", "#%}"),
+        )
+    }
+}
+
+impl Syntax {
+    fn new(
+        logical: (&str, &str),
+        placeholder: (&str, &str),
+        env: (&str, &str),
+        raw: (&str, &str),
+        comment: (&str, &str),
+    ) -> Self {
+        let pair = |(head, tail): (&str, &str)| (head.to_owned(), tail.to_owned());
+        Self {
+            logical: pair(logical),
+            placeholder: pair(placeholder),
+            env: pair(env),
+            raw: pair(raw),
+            comment: pair(comment),
+        }
+    }
+
+    /// Get the preset syntax by name
+    /// - default: `{% %}`, `{{ }}`, `{$ $}`, `{# #}`, `{%# #%}`
+    /// - bracket: `[% %]`, `[[ ]]`, `[$ $]`, `[# #]`, `[%# #%]`
+    /// - angle: `<% %>`, `<< >>`, `<$ $>`, `<# #>`, `<%# #%>`
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self::default()),
+            "bracket" => Some(Self::new(
+                ("[%", "%]"),
+                ("[[", "]]"),
+                ("[$", "$]"),
+                ("[#", "#]"),
+                ("[%#", "#%]"),
+            )),
+            "angle" => Some(Self::new(
+                ("<%", "%>"),
+                ("<<", ">>"),
+                ("<$", "$>"),
+                ("<#", "#>"),
+                ("<%#", "#%>"),
+            )),
+            _ => None,
+        }
+    }
+
+    fn check(&self) -> Result<(), String> {
+        const LOGICAL_IDX: usize = 0;
+        const COMMENT_IDX: usize = 4;
+        let pairs = [
+            &self.logical,
+            &self.placeholder,
+            &self.env,
+            &self.raw,
+            &self.comment,
+        ];
+        for (idx, (head, tail)) in pairs.iter().enumerate() {
+            if head.is_empty() || tail.is_empty() {
+                return Err("Syntax delimiter can not be empty".to_owned());
+            }
+            for (other_idx, (other_head, other_tail)) in pairs[..idx].iter().enumerate() {
+                if other_head == head {
+                    return Err(format!("Syntax delimiter is duplicated: {}", head));
+                }
+                // Which one of the delimiters matches would depend on the order of matching.
+                // The comment head may extend the logical head with `#`, as a logical tag never starts with `#`
+                let is_comment_head = idx == COMMENT_IDX
+                    && other_idx == LOGICAL_IDX
+                    && head.strip_prefix(other_head.as_str()) == Some("#");
+                if is_prefix_overlapped(head, other_head) && !is_comment_head {
+                    return Err(format!(
+                        "Syntax delimiter is a prefix of another: {} and {}",
+                        other_head, head
+                    ));
+                }
+                if is_prefix_overlapped(tail, other_tail) {
+                    return Err(format!(
+                        "Syntax delimiter is a prefix of another: {} and {}",
+                        other_tail, tail
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_prefix_overlapped(delimiter: &str, other: &str) -> bool {
+    delimiter.starts_with(other) || other.starts_with(delimiter)
+}
+
+/// Parse the syntax spec, it is a preset name or the delimiter pairs separated by whitespace,
+/// and the pairs overwrite the preset (or default syntax), for example
+/// ```text
+/// bracket
+/// logical=<% %> placeholder=<< >>
+/// angle env=${ }
+/// ```
+impl FromStr for Syntax {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut syntax = Syntax::default();
+        let mut parts = spec.split_whitespace();
+        while let Some(part) = parts.next() {
+            if let Some((key, head)) = part.split_once('=') {
+                let tail = parts
+                    .next()
+                    .ok_or_else(|| format!("Syntax delimiter missing tail: {}", part))?;
+                let pair = match key {
+                    "logical" => &mut syntax.logical,
+                    "placeholder" => &mut syntax.placeholder,
+                    "env" => &mut syntax.env,
+                    "raw" => &mut syntax.raw,
+                    "comment" => &mut syntax.comment,
+                    _ => return Err(format!("Unsupported syntax symbol: {}", key)),
+                };
+                *pair = (head.to_owned(), tail.to_owned());
+            } else {
+                syntax = Syntax::preset(part)
+                    .ok_or_else(|| format!("Unsupported syntax preset: {}", part))?;
+            }
+        }
+        syntax.check()?;
+        Ok(syntax)
+    }
+}
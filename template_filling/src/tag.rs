@@ -0,0 +1,27 @@
+use std::fmt::Debug;
+
+use serde_json::Value;
+
+use crate::fill::TagContext;
+
+/// Custom tag which is registered by `FillOptions::custom_tags`, like `{% license "MIT" %}`.
+/// A block tag is ended by the end tag named 'end' + name, like `{% region %}...{% endregion %}`
+pub trait CustomTag: Debug {
+    /// Name of the tag, it is the first word in the tag
+    fn name(&self) -> &str;
+
+    /// The tag is a block tag which needs the end tag, default is an inline tag
+    fn has_end_tag(&self) -> bool {
+        false
+    }
+
+    /// Parse the tag body which is the text after the name, it is given to `render` as the arguments.
+    /// An error message if the body is illegal, default is the body as a string
+    fn parse(&self, body: &str) -> Result<Value, String> {
+        Ok(Value::String(body.to_owned()))
+    }
+
+    /// Fill the tag with the arguments parsed by `parse`, the result is indented as the content of tag
+    /// if it is filled by `TagContext::fill_body` or `TagContext::fill_content`
+    fn render(&self, args: &Value, ctx: &mut TagContext) -> String;
+}
@@ -0,0 +1,106 @@
+use serde_json::Value;
+
+use crate::{
+    ast::Ast,
+    check::{self, Diagnostic},
+    error::FillError,
+    fill::{self, RenderMode, RenderOutput},
+    options::FillOptions,
+    vars,
+};
+
+/// Template content with its fill options
+#[derive(Debug, Clone)]
+pub struct Template {
+    content: String,
+    options: FillOptions,
+}
+
+impl Template {
+    pub fn new<T: Into<String>>(content: T) -> Self {
+        Self::with_options(content, FillOptions::default())
+    }
+
+    pub fn with_options<T: Into<String>>(content: T, options: FillOptions) -> Self {
+        Self {
+            content: content.into(),
+            options,
+        }
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    pub fn options(&self) -> &FillOptions {
+        &self.options
+    }
+
+    /// Parse the template into the AST
+    pub fn ast(&self) -> Ast {
+        fill::parse_template(&self.content, &self.options)
+    }
+
+    /// Variable paths referenced by the template, like `custom_fields[].name`
+    pub fn variables(&self) -> Vec<String> {
+        vars::variables(&self.content, &self.options)
+    }
+
+    /// Problems of the template, and the undefined variables if the data is given
+    pub fn check(&self, data: Option<&Value>) -> Vec<Diagnostic> {
+        check::check(&self.content, data, &self.options)
+    }
+
+    /// Fill the whole template
+    pub fn render(&self, data: Option<&Value>) -> String {
+        fill::fill_template_with_options(&self.content, data, &self.options)
+    }
+
+    /// Fill only the first block named `name`, the indent of its content is the same as in the whole filled result.
+    /// None if the block is not filled
+    pub fn render_block(&self, name: &str, data: Option<&Value>) -> Option<String> {
+        fill::fill_template_block(&self.content, name, data, &self.options)
+    }
+
+    /// Fill the files emitted by the tag 'file', and the content out of them is dropped
+    /// @return [(file path, file content)]
+    pub fn render_files(&self, data: Option<&Value>) -> Vec<(String, String)> {
+        fill::fill_template_files(&self.content, data, &self.options).1
+    }
+
+    /// Fill the whole template, it is failed by the tags 'assert' and 'error'
+    pub fn try_render(&self, data: Option<&Value>) -> Result<String, FillError> {
+        fill::try_fill_template_with_options(&self.content, data, &self.options)
+    }
+
+    pub fn try_render_block(
+        &self,
+        name: &str,
+        data: Option<&Value>,
+    ) -> Result<Option<String>, FillError> {
+        fill::try_fill_template_block(&self.content, name, data, &self.options)
+    }
+
+    pub fn try_render_files(
+        &self,
+        data: Option<&Value>,
+    ) -> Result<Vec<(String, String)>, FillError> {
+        fill::try_fill_template_files(&self.content, data, &self.options).map(|(_, files)| files)
+    }
+
+    /// Fill the whole template and keep going past the recoverable problems,
+    /// the marker like `{{Error: message}}` is filled at each failed point
+    pub fn render_with_diagnostics(&self, data: Option<&Value>) -> (String, Vec<Diagnostic>) {
+        fill::fill_template_with_diagnostics(&self.content, data, &self.options)
+    }
+
+    /// Fill the whole template with what is recorded besides the output, like the diagnostics,
+    /// the report and the source map. It is failed by the tags 'assert' and 'error' unless it is recovering
+    pub fn try_render_with_mode(
+        &self,
+        data: Option<&Value>,
+        mode: &RenderMode,
+    ) -> Result<RenderOutput, FillError> {
+        fill::try_fill_template_with_mode(&self.content, data, &self.options, mode)
+    }
+}
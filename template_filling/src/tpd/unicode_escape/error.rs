@@ -9,6 +9,7 @@ use std::fmt;
 
 /// Represents the different types of errors that can occur during decoding.
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum DecodeError {
     /// Indicates an invalid escape sequence was encountered.
     InvalidEscape,
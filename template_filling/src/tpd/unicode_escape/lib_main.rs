@@ -18,7 +18,6 @@
 //!     println!("Decoded string: {}", decoded);
 //! }
 use std::iter::Peekable;
-use std::u32;
 
 use super::DecodeError;
 
@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    ast::{
+        walk_ast, walk_tag, Ast, Condition, EnvNode, Expression, ExpressionKind, Span, Tag,
+        TagNode, Visitor,
+    },
+    fill,
+    options::FillOptions,
+};
+
+/// Env keys which are the options of filling, their values are not variables
+const OPTION_ENV_KEYS: [&str; 3] = ["join_with", "indent_base", "tag_indent"];
+
+/// Variable paths referenced by the template, in the order they first appear.
+/// The name of loop item is attributed to its array, like `custom_fields[].name` of
+/// `{% for field in custom_fields %}{{ field.name }}{% endfor %}`, and the local names
+/// (set, env defines, macro parameters and system variables like `$index`) are skipped.
+/// The included, extended and imported templates are not followed
+pub fn variables<T: AsRef<str>>(template_content: T, options: &FillOptions) -> Vec<String> {
+    let template_ast = fill::parse_template(template_content, options);
+    collect_variables(&template_ast, options)
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect()
+}
+
+/// Variable paths with the span of the symbol where they first appear
+pub(crate) fn collect_variables(template_ast: &Ast, options: &FillOptions) -> Vec<(String, Span)> {
+    let mut collector = VariableCollector {
+        options,
+        scopes: Vec::new(),
+        paths: Vec::new(),
+        span: Span::new(0, 0),
+        nested: 0,
+    };
+    template_ast.accept(&mut collector);
+    collector.paths
+}
+
+/// Skeleton data of the variable paths, the value of each leaf is null
+/// and the array contains one item, like `{"custom_fields": [{"name": null}]}`
+pub fn variables_skeleton(paths: &[String]) -> Value {
+    let mut skeleton = Value::Object(Map::new());
+    for path in paths {
+        insert_path(&mut skeleton, &parse_path(path));
+    }
+    skeleton
+}
+
+/// JSON Schema of the skeleton data, the leaf accepts any value
+pub fn variables_schema(paths: &[String]) -> Value {
+    let mut schema = to_schema(&variables_skeleton(paths));
+    schema.as_object_mut().unwrap().insert(
+        "$schema".to_owned(),
+        json!("https://json-schema.org/draft/2020-12/schema"),
+    );
+    schema
+}
+
+struct VariableCollector<'a> {
+    options: &'a FillOptions,
+    scopes: Vec<Scope>,
+    paths: Vec<(String, Span)>,
+    /// Span of the symbol which is being visited
+    span: Span,
+    /// Depth of the templates in string expressions, their spans are not in the template
+    nested: usize,
+}
+
+#[derive(Default)]
+struct Scope {
+    /// Names defined in the scope, with the paths they refer to, None if it is local
+    bindings: HashMap<String, Option<String>>,
+    /// Path of the data of tag 'with', the names out of the scope are not visible,
+    /// None if the data is local
+    data: Option<Option<String>>,
+}
+
+impl VariableCollector<'_> {
+    /// Path of the variable, None if it is local
+    fn resolve(&self, variable: &str) -> Option<String> {
+        if variable.starts_with('$') {
+            return None;
+        }
+        let (root, rest) = variable.split_at(variable.find('.').unwrap_or(variable.len()));
+        for scope in self.scopes.iter().rev() {
+            if let Some(binding) = scope.bindings.get(root) {
+                return binding.as_ref().map(|path| format!("{}{}", path, rest));
+            }
+            if let Some(data) = &scope.data {
+                return data.as_ref().map(|data| format!("{}.{}", data, variable));
+            }
+        }
+        Some(variable.to_owned())
+    }
+
+    fn enter(&mut self, span: Span) {
+        if self.nested == 0 {
+            self.span = span;
+        }
+    }
+
+    fn bind(&mut self, name: &str, path: Option<String>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.bindings.insert(name.to_owned(), path);
+        }
+    }
+
+    fn record(&mut self, path: Option<String>) {
+        if let Some(path) = path {
+            if !self.paths.iter().any(|(p, _)| *p == path) {
+                self.paths.push((path, self.span));
+            }
+        }
+    }
+
+    /// Record the variable of expression, and the placeholders in the string
+    /// @return Path of the variable
+    fn record_expression(&mut self, expression: &Expression) -> Option<String> {
+        match expression.kind {
+            ExpressionKind::Variable => {
+                let path = self.resolve(&expression.text);
+                self.record(path.clone());
+                path
+            }
+            ExpressionKind::String => {
+                let content = &expression.text[1..expression.text.len() - 1];
+                if content.contains(self.options.syntax.placeholder.0.as_str()) {
+                    self.nested += 1;
+                    fill::parse_template(content, self.options).accept(self);
+                    self.nested -= 1;
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn record_text(&mut self, text: &str) -> Option<String> {
+        Expression::parse(text.trim()).and_then(|expression| self.record_expression(&expression))
+    }
+
+    fn record_condition(&mut self, condition: &Condition) {
+        self.record_expression(&condition.left);
+        if let Some(right) = &condition.right {
+            self.record_expression(right);
+        }
+    }
+
+    /// Arguments like '"id", type, nullable=false'
+    fn record_args(&mut self, args: &str) {
+        for arg in fill::split_outside_quotes(args, ',') {
+            let value = match arg.split_once('=') {
+                Some((name, value)) if !name.contains('"') => value,
+                _ => arg,
+            };
+            self.record_text(value);
+        }
+    }
+}
+
+impl Visitor for VariableCollector<'_> {
+    fn visit_ast(&mut self, ast: &Ast) {
+        self.scopes.push(Scope::default());
+        walk_ast(self, ast);
+        self.scopes.pop();
+    }
+
+    fn visit_env(&mut self, env: &EnvNode) {
+        self.enter(env.span);
+        if !OPTION_ENV_KEYS.contains(&env.key.as_str()) {
+            self.record_text(&env.value);
+        }
+        self.bind(&env.key, None);
+    }
+
+    /// {{ name | filter_1 | filter_2(arg_1, arg_2) }}, the name may be a call like 'macro_name(arg_1)'
+    fn visit_placeholder(&mut self, span: Span, expression: &str) {
+        self.enter(span);
+        let mut parts = fill::split_outside_quotes(expression, '|').into_iter();
+        let name = parts.next().unwrap_or_default().trim();
+        match name.strip_suffix(')').and_then(|call| call.split_once('(')) {
+            Some((_, args)) => self.record_args(args),
+            None => {
+                self.record_text(name);
+            }
+        }
+        for filter in parts {
+            if let Some((_, args)) = filter
+                .trim()
+                .strip_suffix(')')
+                .and_then(|f| f.split_once('('))
+            {
+                self.record_args(args);
+            }
+        }
+    }
+
+    fn visit_tag(&mut self, span: Span, tag_node: &TagNode) {
+        self.enter(span);
+        self.scopes.push(Scope::default());
+        match &tag_node.tag {
+            Tag::For {
+                item, value, array, ..
+            } => {
+                let path = self.record_text(array);
+                match value {
+                    // The key is not a data path, and the keys of value are unknown
+                    Some(value) => {
+                        self.bind(item, None);
+                        self.bind(value, None);
+                    }
+                    None => self.bind(item, path.map(|path| format!("{}[]", path))),
+                }
+            }
+            Tag::If { condition } => self.record_condition(condition),
+            Tag::Assert { condition, message } => {
+                self.record_condition(condition);
+                self.record_expression(message);
+            }
+            Tag::Error { message } => {
+                self.record_expression(message);
+            }
+            Tag::Include { template, with } => {
+                self.record_expression(template);
+                if let Some(with) = with {
+                    self.record_text(with);
+                }
+            }
+            Tag::Extends { template } | Tag::Import { template, .. } => {
+                self.record_expression(template);
+            }
+            Tag::Macro { params, .. } => {
+                for param in params {
+                    if let Some(default) = &param.default {
+                        self.record_expression(default);
+                    }
+                    self.bind(&param.name, None);
+                }
+            }
+            Tag::Match { subject } => {
+                self.record_expression(subject);
+            }
+            Tag::Case { values } => {
+                for value in values {
+                    self.record_expression(value);
+                }
+            }
+            Tag::With { bindings } => {
+                for (name, expression) in bindings {
+                    let path = self.record_text(expression);
+                    self.bind(name, path);
+                }
+            }
+            Tag::WithData { variable } => {
+                let path = self.record_text(variable);
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.data = Some(path);
+                }
+            }
+            Tag::File { path } => {
+                self.record_expression(path);
+            }
+            Tag::Block { .. } | Tag::Set { .. } | Tag::Default | Tag::Custom { .. } => {}
+        }
+        walk_tag(self, tag_node);
+        self.scopes.pop();
+        // The captured content is set out of the tag
+        if let Tag::Set { name } = &tag_node.tag {
+            self.bind(name, None);
+        }
+    }
+}
+
+/// The path is defined if it can be found in the data, the item path like `columns[].name`
+/// is defined if any item has it, or the array is empty
+pub(crate) fn is_path_defined(data: &Value, path: &str) -> bool {
+    is_defined(data, &parse_path(path))
+}
+
+fn is_defined(value: &Value, segments: &[PathSegment]) -> bool {
+    let Some((segment, rest)) = segments.split_first() else {
+        return true;
+    };
+    match (segment, value) {
+        (PathSegment::Key(key), Value::Object(object)) => object
+            .get(*key)
+            .is_some_and(|child| is_defined(child, rest)),
+        (PathSegment::Item, Value::Array(array)) => {
+            array.is_empty() || array.iter().any(|item| is_defined(item, rest))
+        }
+        _ => false,
+    }
+}
+
+enum PathSegment<'a> {
+    Key(&'a str),
+    /// Item of array, `[]`
+    Item,
+}
+
+/// Path like 'tables[].columns[].name'
+fn parse_path(path: &str) -> Vec<PathSegment<'_>> {
+    let mut segments = Vec::new();
+    for key in path.split('.') {
+        let mut key = key;
+        let mut items = 0;
+        while let Some(stripped) = key.strip_suffix("[]") {
+            key = stripped;
+            items += 1;
+        }
+        segments.push(PathSegment::Key(key));
+        segments.extend((0..items).map(|_| PathSegment::Item));
+    }
+    segments
+}
+
+/// The leaf is replaced by the object or array if a longer path is inserted
+fn insert_path(value: &mut Value, segments: &[PathSegment]) {
+    let Some((segment, rest)) = segments.split_first() else {
+        return;
+    };
+    match segment {
+        PathSegment::Key(key) => {
+            if !value.is_object() {
+                *value = Value::Object(Map::new());
+            }
+            let child = value
+                .as_object_mut()
+                .unwrap()
+                .entry(key.to_string())
+                .or_insert(Value::Null);
+            insert_path(child, rest);
+        }
+        PathSegment::Item => {
+            if !value.is_array() {
+                *value = json!([null]);
+            }
+            insert_path(&mut value.as_array_mut().unwrap()[0], rest);
+        }
+    }
+}
+
+fn to_schema(value: &Value) -> Value {
+    match value {
+        Value::Object(object) => json!({
+            "type": "object",
+            "properties": object
+                .iter()
+                .map(|(key, value)| (key.to_owned(), to_schema(value)))
+                .collect::<Map<String, Value>>(),
+        }),
+        Value::Array(array) => json!({
+            "type": "array",
+            "items": array.first().map_or(json!({}), to_schema),
+        }),
+        _ => json!({}),
+    }
+}
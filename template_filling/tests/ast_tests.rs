@@ -0,0 +1,126 @@
+use template_filling::{
+    ast::{walk_tag, ExpressionKind, NodeKind, Span, Tag, TagNode, Visitor},
+    parse, FillOptions, Syntax, Template,
+};
+
+const TEMPLATE: &str = r#"{$ join_with = ,\n $}
+CREATE TABLE {{ name }} (
+    {% for column in columns %}
+    {{ column.name }} {{ column.type }}
+    {% endfor %}
+);"#;
+
+#[test]
+fn test_parse() {
+    let ast = parse(TEMPLATE, &FillOptions::default());
+    assert_eq!(ast.envs.len(), 1);
+    assert_eq!(ast.envs[0].key, "join_with");
+    assert_eq!(ast.envs[0].value, ",\\n");
+    assert_eq!(ast.envs[0].span.text(TEMPLATE), "{$ join_with = ,\\n $}");
+    // The line which only contains env defines is not a line
+    assert_eq!(ast.lines.len(), 3);
+    assert_eq!(ast.lines[0].line_feed.as_deref(), Some("\n"));
+
+    let for_line = &ast.lines[1];
+    assert_eq!(for_line.indent_width, 4);
+    assert_eq!(for_line.indent[0].text(TEMPLATE), "    ");
+    let for_node = &for_line.nodes[0];
+    assert!(for_node.first_in_line);
+    assert_eq!(for_node.span.text(TEMPLATE), "{% for column in columns %}");
+    let NodeKind::Tag(tag_node) = &for_node.kind else {
+        panic!("for is not a tag")
+    };
+    assert_eq!(
+        tag_node.tag,
+        Tag::For {
+            item: "column".to_owned(),
+            value: None,
+            array: "columns".to_owned(),
+            recursive: false
+        }
+    );
+    assert_eq!(tag_node.end_span.unwrap().text(TEMPLATE), "{% endfor %}");
+    let body = tag_node.body.as_ref().unwrap();
+    assert_eq!(body.min_indent_width, Some(4));
+    assert_eq!(
+        body.lines[0].nodes[0].kind,
+        NodeKind::Placeholder {
+            expression: "column.name".to_owned()
+        }
+    );
+    assert!(!body.lines[0].nodes[1].first_in_line);
+    assert_eq!(ast.lines[2].nodes[0].span.text(TEMPLATE), ");");
+    assert_eq!(ast.lines[2].line_feed, None);
+}
+
+#[test]
+fn test_parse_with_syntax() {
+    let options = FillOptions {
+        syntax: Syntax::preset("bracket").unwrap(),
+        ..FillOptions::default()
+    };
+    let content = "[% include \"a.tmpl\" with table %]";
+    let ast = Template::with_options(content, options).ast();
+    let NodeKind::Tag(tag_node) = &ast.lines[0].nodes[0].kind else {
+        panic!("include is not a tag")
+    };
+    assert_eq!(ast.lines[0].nodes[0].span, Span::new(0, content.len()));
+    assert_eq!(tag_node.body, None);
+    assert_eq!(tag_node.end_span, None);
+    let Tag::Include { template, with } = &tag_node.tag else {
+        panic!("tag is not include")
+    };
+    assert_eq!(template.kind, ExpressionKind::String);
+    assert_eq!(template.text, "\"a.tmpl\"");
+    assert_eq!(with.as_deref(), Some("table"));
+}
+
+/// Collect the variables used by placeholders and loops
+#[derive(Default)]
+struct VariableCollector {
+    variables: Vec<String>,
+}
+
+impl Visitor for VariableCollector {
+    fn visit_placeholder(&mut self, _span: Span, expression: &str) {
+        self.variables.push(expression.to_owned());
+    }
+
+    fn visit_tag(&mut self, _span: Span, tag_node: &TagNode) {
+        if let Tag::For { array, .. } = &tag_node.tag {
+            self.variables.push(array.to_owned());
+        }
+        walk_tag(self, tag_node);
+    }
+}
+
+#[test]
+fn test_visitor() {
+    let mut collector = VariableCollector::default();
+    parse(TEMPLATE, &FillOptions::default()).accept(&mut collector);
+    assert_eq!(
+        collector.variables,
+        vec!["name", "columns", "column.name", "column.type"]
+    );
+}
+
+#[test]
+fn test_parse_match_arms() {
+    let content = "{% match kind %}{% case \"a\", 1 %}A{% default %}B{% endmatch %}";
+    let ast = parse(content, &FillOptions::default());
+    let NodeKind::Tag(match_node) = &ast.lines[0].nodes[0].kind else {
+        panic!("match is not a tag")
+    };
+    assert_eq!(match_node.end_span.unwrap().text(content), "{% endmatch %}");
+    let arms = &match_node.body.as_ref().unwrap().lines[0].nodes;
+    assert_eq!(arms.len(), 2);
+    assert_eq!(arms[0].span.text(content), "{% case \"a\", 1 %}");
+    let NodeKind::Tag(case_node) = &arms[0].kind else {
+        panic!("case is not a tag")
+    };
+    assert_eq!(case_node.end_span, None);
+    let Tag::Case { values } = &case_node.tag else {
+        panic!("tag is not case")
+    };
+    assert_eq!(values[1].kind, ExpressionKind::Number);
+}
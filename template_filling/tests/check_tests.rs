@@ -0,0 +1,214 @@
+use std::rc::Rc;
+
+use serde_json::{json, Value};
+use template_filling::{check, CustomTag, FillOptions, Severity, TagContext, Template};
+
+/// Block tag, like `{% region name %}...{% endregion %}`
+#[derive(Debug)]
+struct Region;
+
+impl CustomTag for Region {
+    fn name(&self) -> &str {
+        "region"
+    }
+
+    fn has_end_tag(&self) -> bool {
+        true
+    }
+
+    fn render(&self, _args: &Value, ctx: &mut TagContext) -> String {
+        ctx.fill_body()
+    }
+}
+
+fn messages(template: &str) -> Vec<String> {
+    check(template, None, &FillOptions::default())
+        .into_iter()
+        .map(|diagnostic| diagnostic.to_string())
+        .collect()
+}
+
+#[test]
+fn test_check_fine() {
+    let template = r#"{% for column in columns %}
+{$ join_with = ,\n $}
+{{ column.name | indent(4) }}{% if column.nullable %} NULL{% endif %}
+{% endfor %}"#;
+    assert!(messages(template).is_empty());
+}
+
+#[test]
+fn test_check_every_tag_form() {
+    let templates = [
+        "{% for column in columns %}{{ column.name }}{% endfor %}",
+        "{% for node in nodes recursive %}{{ node.name }}{{ loop(node.children) }}{% endfor %}",
+        "{% for key, value in user %}{{ key }}={{ value }}{% endfor %}",
+        "{% if a %}a{% endif %}{% if a == \"b\" %}b{% endif %}{% if a != b %}c{% endif %}",
+        "{% if a is defined %}a{% endif %}{% if a is not defined %}b{% endif %}",
+        "{% include \"column.tmpl\" %}{% include \"column.tmpl\" with column %}",
+        "{% extends \"base.tmpl\" %}{% block body %}body{% endblock %}",
+        "{% macro column(name, nullable=true) %}{{ name }}{% endmacro %}{{ column(\"id\") }}",
+        "{% import \"macros.tmpl\" %}{% import \"macros.tmpl\" as m %}{{ m.pk(\"id\") }}",
+        "{% set kind %}table{% endset %}{{ kind }}",
+        "{% match kind %}{% case \"a\", \"b\" %}ab{% case \"c\" %}c{% default %}d{% endmatch %}",
+        "{% with table %}{{ name }}{% endwith %}{% with pk = table.pk, n = table.name %}{{ pk }}{% endwith %}",
+        "{% file \"{{ name }}.sql\" %}{{ name }}{% endfile %}",
+        "{% assert a is defined %}{% assert a == b, \"no {{ a }}\" %}{% error \"no {{ a }}\" %}",
+        "{% region users %}{{ name }}{% endregion %}",
+    ];
+    let options = FillOptions {
+        custom_tags: vec![Rc::new(Region)],
+        ..Default::default()
+    };
+    for template in templates {
+        let diagnostics: Vec<String> = check(template, None, &options)
+            .into_iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect();
+        assert!(diagnostics.is_empty(), "{}: {:?}", template, diagnostics);
+    }
+}
+
+#[test]
+fn test_check_key_value_loop_variables() {
+    let data = json!({ "user": { "name": "Leung", "age": 18 } });
+    let template = Template::new("{% for k, v in user %}{{ k }}={{ v }}{{ v.x }}{% endfor %}");
+    assert!(template.check(Some(&data)).is_empty());
+}
+
+#[test]
+fn test_check_unbalanced_tags() {
+    assert_eq!(
+        messages("{% if a %}\n{% endfor %}\n{% endif %}\n{% endif %}\n{% for a in b %}"),
+        vec![
+            "2:1: Tag must be balanced, endfor does not close if",
+            "4:1: Missing head tag of endif",
+            "5:1: Unclosed tag for, missing endfor",
+        ]
+    ); // They fail the filling, so they are errors
+    assert!(check(
+        "{% endif %}\n{% for a in b %}",
+        None,
+        &FillOptions::default()
+    )
+    .iter()
+    .all(|diagnostic| diagnostic.severity == Severity::Error));
+}
+
+#[test]
+fn test_check_unclosed_match() {
+    assert_eq!(
+        messages("{% match a %}\n{% case 1 %}\none\n{% default %}\nother"),
+        vec!["1:1: Unclosed tag match, missing endmatch"]
+    );
+}
+
+#[test]
+fn test_check_illegal_tags() {
+    assert_eq!(
+        messages("{% loop a %}\n  {% for a of b %}\n{% case 1 %}"),
+        vec![
+            "1:1: Unsupported tag: loop a",
+            "2:3: Illegal expression: for",
+            "3:1: Tag case and default must be in match",
+        ]
+    );
+}
+
+#[test]
+fn test_check_illegal_head_tag_with_end_tag() {
+    assert_eq!(
+        messages(
+            "{% for a of b %}
+{{ a }}
+{% endfor %}
+{% match %}
+{% case 1 %}
+{% endmatch %}"
+        ),
+        vec![
+            "1:1: Illegal expression: for",
+            "4:1: Unsupported tag: match",
+        ]
+    );
+}
+
+#[test]
+fn test_check_illegal_placeholders() {
+    assert_eq!(
+        messages("{{ 1a }} {{ name | upper }} {{ name | indent(4 }} {{ m(1b) }}"),
+        vec![
+            "1:1: Unvalid variable name: 1a",
+            "1:10: Unsupported filter: upper",
+            "1:29: Illegal filter: indent(4",
+            "1:51: Unvalid variable name: 1b",
+        ]
+    );
+}
+
+#[test]
+fn test_check_unterminated_symbols() {
+    assert_eq!(
+        messages("{$ indent_base $}\nvalue {{ name"),
+        vec![
+            "1:1: Env symbol missing '=', it should be define like '{$ key = value $}'",
+            "2:7: Unterminated symbol {{, missing }}",
+        ]
+    );
+}
+
+#[test]
+fn test_check_undefined_variables() {
+    let data = json!({
+        "table": "user",
+        "columns": [{ "name": "id" }, { "name": "name", "comment": "Name" }],
+        "indexes": []
+    });
+    let template = Template::new(
+        r#"CREATE TABLE {{ table }} (
+    {% for column in columns %}
+    {{ column.name }} {{ column.type }} COMMENT '{{ column.comment }}'
+    {% endfor %}
+);
+{% for index in indexes %}{{ index.name }}{% endfor %}
+{{ engine }}"#,
+    );
+    let diagnostics: Vec<String> = template
+        .check(Some(&data))
+        .into_iter()
+        .map(|diagnostic| diagnostic.to_string())
+        .collect();
+    assert_eq!(
+        diagnostics,
+        vec![
+            "3:23: Undefined variable: columns[].type",
+            "7:1: Undefined variable: engine",
+        ]
+    );
+}
+
+#[test]
+fn test_check_severity_and_hints() {
+    let diagnostics = check(
+        "{% if a %}\n{% endfi %}\n{% endfor %}\n{{ a | indnet }}\n{% for a b %}",
+        None,
+        &FillOptions::default(),
+    );
+    let hints: Vec<(Severity, Option<&str>)> = diagnostics
+        .iter()
+        .map(|diagnostic| (diagnostic.severity, diagnostic.hint.as_deref()))
+        .collect();
+    assert_eq!(
+        hints,
+        vec![
+            (Severity::Error, Some("Add `endif` after its body")),
+            (Severity::Error, Some("Did you mean `endif`?")),
+            (Severity::Error, Some("Did you mean `endif`?")),
+            (Severity::Error, Some("Did you mean `indent`?")),
+            (
+                Severity::Error,
+                Some("Usage: `for item in array [recursive] | for key, value in object`")
+            ),
+        ]
+    );
+}
@@ -0,0 +1,54 @@
+use serde_json::json;
+use template_filling::{fill, fill_with_options, FillOptions, Syntax};
+
+#[test]
+fn test_comment() {
+    assert_eq!(fill("a{%# note #%}b", None), "ab");
+    assert_eq!(fill("a{%# {{ x }} {% if %} #%}b", None), "ab");
+    assert_eq!(fill("a\n{%# note #%}\nb", None), "a\nb");
+    assert_eq!(fill("a\n    {%# note #%}\nb", None), "a\nb");
+    assert_eq!(fill("a\r\n{%# line 1\nline 2 #%}\r\nb", None), "a\r\nb");
+    assert_eq!(fill("a {%# note #%}\nb", None), "a \nb");
+    assert_eq!(fill("{# raw #}{%# comment #%}", None), " raw ");
+}
+
+#[test]
+fn test_raw_starts_with_hash() {
+    // The raw text which starts with '#' is not a comment
+    assert_eq!(fill("{## heading #}", None), "# heading ");
+    assert_eq!(
+        fill(
+            "a
+{## heading ##}
+b",
+            None
+        ),
+        "a
+# heading #
+b"
+    );
+}
+
+#[test]
+fn test_comment_in_tag() {
+    let data = json!({ "arrays": [1, 2, 3] });
+    assert_eq!(
+        fill(
+            "{% for i in arrays %}\n{%# note #%}\n{$ join_with = , $}\n{{ i }}\n{% endfor %}",
+            Some(&data)
+        ),
+        "1,2,3"
+    );
+}
+
+#[test]
+fn test_comment_syntax() {
+    let options = FillOptions {
+        syntax: Syntax::preset("bracket").unwrap(),
+        ..Default::default()
+    };
+    assert_eq!(
+        fill_with_options("a\n[%# note #%]\nb", None, &options),
+        "a\nb"
+    );
+}
@@ -0,0 +1,61 @@
+use serde_json::json;
+use template_filling::{fill, fill_with_options, FillOptions};
+
+#[test]
+fn test_indent_filter() {
+    let data = json!({ "sql": "SELECT *\nFROM t\n\nWHERE 1 = 1", "one_line": "abc" });
+    assert_eq!(
+        fill("(\n    {{ sql | indent }}\n)", Some(&data)),
+        "(\n    SELECT *\n    FROM t\n\n    WHERE 1 = 1\n)"
+    );
+    assert_eq!(
+        fill("  x = {{ sql | indent(2) }}", Some(&data)),
+        "  x = SELECT *\n  FROM t\n\n  WHERE 1 = 1"
+    );
+    assert_eq!(
+        fill(r#"{{ sql | indent("-- ") }}"#, Some(&data)),
+        "SELECT *\n-- FROM t\n\n-- WHERE 1 = 1"
+    );
+    assert_eq!(fill("    {{ one_line | indent }}", Some(&data)), "    abc");
+    assert_eq!(
+        fill("{{ missing | indent }}", Some(&data)),
+        "{{missing | indent: Not found}}"
+    );
+}
+
+#[test]
+fn test_indent_filter_in_tag() {
+    let data = json!({ "items": ["a\nb", "c\nd"] });
+    assert_eq!(
+        fill(
+            "begin\n  {% for item in items %}\n    {$ join_with = \\n $}\n    {{ item | indent }}\n  {% endfor %}\nend",
+            Some(&data)
+        ),
+        "begin\n  a\n  b\n  c\n  d\nend"
+    );
+}
+
+#[test]
+fn test_auto_indent() {
+    let options = FillOptions {
+        auto_indent: true,
+        ..Default::default()
+    };
+    let data = json!({ "doc": "/**\n * Doc\n */" });
+    assert_eq!(
+        fill_with_options("class A {\n    {{ doc }}\n}", Some(&data), &options),
+        "class A {\n    /**\n     * Doc\n     */\n}"
+    );
+    assert_eq!(
+        fill("class A {\n    {{ doc }}\n}", Some(&data)),
+        "class A {\n    /**\n * Doc\n */\n}"
+    );
+    assert_eq!(
+        fill_with_options(
+            "{% if true %}\n  {$ indent_base = \"raw\" $}\n      {{ doc }}\n{% endif %}",
+            Some(&data),
+            &options
+        ),
+        "      /**\n       * Doc\n       */"
+    );
+}
@@ -0,0 +1,82 @@
+use serde_json::json;
+use template_filling::{fill, fill_with_options, FillOptions, IndentStyle};
+
+#[test]
+fn test_mixed_indent_in_tag() {
+    let data = json!({ "arrays": [1, 2] });
+    // Tab is 4 columns, so the relative indent of the second line is 2 columns
+    assert_eq!(
+        fill(
+            "{% for i in arrays %}\n\t{$ join_with = \\n $}\n\t  a{{ i }}\n\tb\n{% endfor %}",
+            Some(&data)
+        ),
+        "  a1\nb\n  a2\nb"
+    );
+    // Tab across the minimum indent is filled by spaces
+    assert_eq!(
+        fill(
+            "{% for i in arrays %}\n  {$ join_with = \\n $}\n  a{{ i }}\n\tb\n{% endfor %}",
+            Some(&data)
+        ),
+        "a1\n  b\na2\n  b"
+    );
+}
+
+#[test]
+fn test_full_width_space_indent() {
+    let data = json!({ "arrays": [1, 2] });
+    assert_eq!(
+        fill(
+            "{% for i in arrays %}\n\u{3000}{$ join_with = \\n $}\n\u{3000}a{{ i }}\n\u{3000}\u{3000}b\n{% endfor %}",
+            Some(&data)
+        ),
+        "a1\n\u{3000}b\na2\n\u{3000}b"
+    );
+    assert_eq!(
+        fill(
+            "{% for i in arrays %}\n  {$ join_with = \\n $}\n  a{{ i }}\n\u{3000}\u{3000}b\n{% endfor %}",
+            Some(&data)
+        ),
+        "a1\n\u{3000}b\na2\n\u{3000}b"
+    );
+}
+
+#[test]
+fn test_tab_width() {
+    let data = json!({ "arrays": [1] });
+    let options = FillOptions {
+        tab_width: 2,
+        ..Default::default()
+    };
+    assert_eq!(
+        fill_with_options(
+            "{% for i in arrays %}\n  a{{ i }}\n\t\tb\n{% endfor %}",
+            Some(&data),
+            &options
+        ),
+        "a1\n\tb"
+    );
+}
+
+#[test]
+fn test_indent_style() {
+    let template = "a\n\tb\n    c\n\t  d\n\r\n";
+    let options = FillOptions {
+        indent_style: IndentStyle::Spaces(2),
+        ..Default::default()
+    };
+    assert_eq!(
+        fill_with_options(template, None, &options),
+        "a\n  b\n  c\n    d\n\r\n"
+    );
+    let options = FillOptions {
+        indent_style: IndentStyle::Tabs,
+        ..Default::default()
+    };
+    assert_eq!(
+        fill_with_options(template, None, &options),
+        "a\n\tb\n\tc\n\t  d\n\r\n"
+    );
+    assert_eq!("spaces:2".parse(), Ok(IndentStyle::Spaces(2)));
+    assert!("spaces".parse::<IndentStyle>().is_err());
+}
@@ -0,0 +1,97 @@
+use serde_json::json;
+use template_filling::{fill_with_options, FillOptions, LineEnding, TrailingNewline};
+
+fn options(line_ending: LineEnding, trailing_newline: TrailingNewline) -> FillOptions {
+    FillOptions {
+        line_ending,
+        trailing_newline,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_line_ending() {
+    let data = json!({ "text": "x\ny", "arrays": [1, 2] });
+    let template =
+        "a\r\n{{ text }}\n{% for i in arrays %}{$ join_with = \\n $}{{ i }}{% endfor %}\r\nb";
+    assert_eq!(
+        fill_with_options(
+            template,
+            Some(&data),
+            &options(LineEnding::Keep, TrailingNewline::Keep)
+        ),
+        "a\r\nx\ny\n1\n2\r\nb"
+    );
+    assert_eq!(
+        fill_with_options(
+            template,
+            Some(&data),
+            &options(LineEnding::Lf, TrailingNewline::Keep)
+        ),
+        "a\nx\ny\n1\n2\nb"
+    );
+    assert_eq!(
+        fill_with_options(
+            template,
+            Some(&data),
+            &options(LineEnding::Crlf, TrailingNewline::Keep)
+        ),
+        "a\r\nx\r\ny\r\n1\r\n2\r\nb"
+    );
+    assert_eq!(
+        fill_with_options(
+            template,
+            Some(&data),
+            &options(LineEnding::Dominant, TrailingNewline::Keep)
+        ),
+        "a\r\nx\r\ny\r\n1\r\n2\r\nb"
+    );
+    assert_eq!(
+        fill_with_options(
+            "a\nb\r\nc\n{{ text }}",
+            Some(&data),
+            &options(LineEnding::Dominant, TrailingNewline::Keep)
+        ),
+        "a\nb\nc\nx\ny"
+    );
+}
+
+#[test]
+fn test_trailing_newline() {
+    assert_eq!(
+        fill_with_options(
+            "a\r\nb",
+            None,
+            &options(LineEnding::Keep, TrailingNewline::Ensure)
+        ),
+        "a\r\nb\r\n"
+    );
+    assert_eq!(
+        fill_with_options(
+            "a\nb\n",
+            None,
+            &options(LineEnding::Keep, TrailingNewline::Ensure)
+        ),
+        "a\nb\n"
+    );
+    assert_eq!(
+        fill_with_options("", None, &options(LineEnding::Lf, TrailingNewline::Ensure)),
+        ""
+    );
+    assert_eq!(
+        fill_with_options(
+            "a\nb\r\n\n",
+            None,
+            &options(LineEnding::Keep, TrailingNewline::Strip)
+        ),
+        "a\nb"
+    );
+    assert_eq!(
+        fill_with_options(
+            "a\nb",
+            None,
+            &options(LineEnding::Crlf, TrailingNewline::Ensure)
+        ),
+        "a\r\nb\r\n"
+    );
+}
@@ -0,0 +1,175 @@
+use std::rc::Rc;
+
+use serde_json::json;
+use template_filling::{fill_with_diagnostics, FillOptions, MemoryLoader, Severity, Template};
+
+fn recover(template: &str, data: serde_json::Value) -> (String, Vec<String>) {
+    let (filled, diagnostics) =
+        fill_with_diagnostics(template, Some(&data), &FillOptions::default());
+    (
+        filled,
+        diagnostics
+            .into_iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect(),
+    )
+}
+
+#[test]
+fn test_recover_missing_variables() {
+    let (filled, problems) = recover(
+        "name: {{ name }}\ntype: {{ type }}\n{% for c in columns %}{{ c }}{% endfor %}",
+        json!({ "name": "id" }),
+    );
+    assert_eq!(filled, "name: id\ntype: {{type: Not found}}\n");
+    assert_eq!(
+        problems,
+        vec![
+            "2:7: Variable not found: type",
+            "3:1: Variable not found: columns",
+        ]
+    );
+}
+
+#[test]
+fn test_recover_bad_comparison() {
+    let (filled, problems) = recover(
+        "{% if kind == \"table\" %}table{% endif %}{% if nullable %} NULL{% endif %}",
+        json!({}),
+    );
+    assert_eq!(filled, "");
+    // The undefined variable in the truthy condition is not a problem
+    assert_eq!(
+        problems,
+        vec!["1:1: Variable not found in comparison: kind"]
+    );
+}
+
+#[test]
+fn test_recover_invalid_escape() {
+    let (filled, problems) = recover("{$ sep = \\u{zz} $}\n[{{ sep }}]", json!({}));
+    assert_eq!(filled, "[{{sep: Not found}}]");
+    assert_eq!(problems.len(), 2);
+    assert!(problems[0].starts_with("1:1: Unicode escape error: \\u{zz}"));
+    assert_eq!(problems[1], "2:2: Variable not found: sep");
+}
+
+#[test]
+fn test_recover_bad_env_key() {
+    let (filled, problems) = recover(
+        "{$ 1a = b $}{$ \"a\" = b $}\n{$ sep = , $}[{{ sep }}]",
+        json!({}),
+    );
+    assert_eq!(filled, "[,]");
+    assert_eq!(
+        problems,
+        vec![
+            "1:1: Token::Env define error: 1a",
+            "1:13: Token::Env define error: \"a\"",
+        ]
+    );
+}
+
+#[test]
+fn test_recover_unsupported_operator() {
+    let (filled, problems) = recover(
+        "{% if a < b %}a{% endif %}{% if a >= b %}b{% endif %}c",
+        json!({ "a": 1, "b": 2 }),
+    );
+    assert_eq!(filled, "abc");
+    assert_eq!(
+        problems,
+        vec![
+            "1:1: Illegal expression: if",
+            "1:27: Illegal expression: if",
+        ]
+    );
+}
+
+#[test]
+fn test_recover_illegal_loop_children() {
+    let (filled, problems) = recover(
+        "{% for n in nodes recursive %}{{ n }}{{ loop(1a) }}{% endfor %}",
+        json!({ "nodes": [1] }),
+    );
+    assert_eq!(filled, "1");
+    assert_eq!(problems, vec!["1:38: Unvalid variable name: 1a"]);
+}
+
+#[test]
+fn test_recover_runtime_errors() {
+    let (filled, problems) = recover(
+        "{{ name | upper }}, {% with table %}{{ name }}{% endwith %}, {{ name }}",
+        json!({ "name": "id" }),
+    );
+    assert_eq!(
+        filled,
+        "{{Error: Unsupported filter: upper}}, {{Error: With data not found: table}}, id"
+    );
+    assert_eq!(
+        problems,
+        vec![
+            "1:1: Unsupported filter: upper",
+            "1:21: With data not found: table",
+        ]
+    );
+}
+
+#[test]
+fn test_recover_error_tags() {
+    let template = Template::new(
+        "{% assert name is defined, \"name is required\" %}\n{% error \"no {{ kind }}\" %}\nend",
+    );
+    let (filled, diagnostics) = template.render_with_diagnostics(Some(&json!({})));
+    assert_eq!(
+        filled,
+        "{{Error: name is required}}\n{{Error: no {{kind: Not found}}}}\nend"
+    );
+    let problems: Vec<(Severity, String)> = diagnostics
+        .into_iter()
+        .map(|diagnostic| (diagnostic.severity, diagnostic.to_string()))
+        .collect();
+    assert_eq!(
+        problems,
+        vec![
+            (Severity::Error, String::from("1:1: name is required")),
+            (
+                Severity::Warning,
+                String::from("2:1: Variable not found: kind")
+            ),
+            (Severity::Error, String::from("2:1: no {{kind: Not found}}")),
+        ]
+    );
+}
+
+#[test]
+fn test_recover_in_include() {
+    let mut loader = MemoryLoader::new();
+    loader.insert("column.tmpl", "{{ name }} {{ type }}\n{% endfor %}");
+    let options = FillOptions {
+        loader: Some(Rc::new(loader)),
+        ..FillOptions::default()
+    };
+    let (filled, diagnostics) = fill_with_diagnostics(
+        "({% include \"column.tmpl\" %}{% include \"missing.tmpl\" %})",
+        Some(&json!({ "name": "id" })),
+        &options,
+    );
+    assert_eq!(
+        filled,
+        "(id {{type: Not found}}{{Error: Load template fail: missing.tmpl, Template not found: missing.tmpl}})"
+    );
+    let problems: Vec<String> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.to_string())
+        .collect();
+    assert_eq!(
+        problems,
+        vec![
+            "column.tmpl:2:1: Missing head tag of endfor",
+            "column.tmpl:1:12: Variable not found: type",
+            "1:29: Load template fail: missing.tmpl, Template not found: missing.tmpl",
+        ]
+    );
+    assert_eq!(diagnostics[1].template.as_deref(), Some("column.tmpl"));
+}
@@ -0,0 +1,70 @@
+use std::rc::Rc;
+
+use serde_json::json;
+use template_filling::{FillOptions, IndentStyle, MemoryLoader, Template};
+
+const DDL: &str = r#"CREATE TABLE {{ name }} (
+    {% block columns %}
+    id INT,
+    name TEXT
+    {% endblock %}
+);
+{% block indexes %}
+CREATE INDEX idx_{{ name }}_name ON {{ name }} (name);
+{% endblock %}"#;
+
+#[test]
+fn test_render() {
+    let data = json!({ "name": "user" });
+    assert_eq!(
+        Template::new(DDL).render(Some(&data)),
+        "CREATE TABLE user (\n    id INT,\n    name TEXT\n);\nCREATE INDEX idx_user_name ON user (name);"
+    );
+}
+
+#[test]
+fn test_render_block() {
+    let data = json!({ "name": "user" });
+    let template = Template::new(DDL);
+    assert_eq!(
+        template.render_block("indexes", Some(&data)).unwrap(),
+        "CREATE INDEX idx_user_name ON user (name);"
+    );
+    // Indent is the same as in the whole filled result
+    assert_eq!(
+        template.render_block("columns", Some(&data)).unwrap(),
+        "    id INT,\n    name TEXT"
+    );
+    assert_eq!(template.render_block("missing", Some(&data)), None);
+}
+
+#[test]
+fn test_render_block_with_options() {
+    let options = FillOptions {
+        indent_style: IndentStyle::Tabs,
+        ..FillOptions::default()
+    };
+    let template = Template::with_options(DDL, options);
+    assert_eq!(
+        template.render_block("columns", None).unwrap(),
+        "\tid INT,\n\tname TEXT"
+    );
+}
+
+#[test]
+fn test_render_block_overridden() {
+    let mut loader = MemoryLoader::new();
+    loader.insert("base.tmpl", DDL);
+    let options = FillOptions {
+        loader: Some(Rc::new(loader)),
+        ..FillOptions::default()
+    };
+    let template = Template::with_options(
+        "{% extends \"base.tmpl\" %}\n{% block columns %}\n{{ super() }},\nage INT\n{% endblock %}",
+        options,
+    );
+    assert_eq!(
+        template.render_block("columns", None).unwrap(),
+        "    id INT,\n    name TEXT,\n    age INT"
+    );
+}
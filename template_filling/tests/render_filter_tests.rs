@@ -0,0 +1,59 @@
+use serde_json::json;
+use template_filling::{fill, fill_with_options, FillOptions};
+
+#[test]
+fn test_render() {
+    let data = json!({
+        "dialect": "mysql",
+        "comments": { "mysql": "Generated for {{ table.name }}", "pg": "-- {{ table.name }}" },
+        "tables": [{ "name": "user" }, { "name": "log" }]
+    });
+    assert_eq!(
+        fill(
+            r#"{% for table in tables %}
+{$ join_with = \n $}
+/* {{ comments.mysql | render }} */
+{% endfor %}"#,
+            Some(&data)
+        ),
+        "/* Generated for user */\n/* Generated for log */"
+    );
+}
+
+#[test]
+fn test_render_nested() {
+    let data = json!({
+        "name": "user",
+        "title": "Table {{ name }}",
+        "header": "-- {{ title | render }}",
+        "flag": true,
+        "condition": "{% if flag %}yes{% endif %}"
+    });
+    assert_eq!(
+        fill(
+            "{{ header | render }}\n{{ condition | render }}",
+            Some(&data)
+        ),
+        "-- Table user\nyes"
+    );
+}
+
+#[test]
+fn test_render_with_indent() {
+    let data = json!({ "body": "id INT,\n{{ column }} TEXT", "column": "name" });
+    assert_eq!(
+        fill("(\n    {{ body | render | indent }}\n)", Some(&data)),
+        "(\n    id INT,\n    name TEXT\n)"
+    );
+}
+
+#[test]
+#[should_panic(expected = "Recursion limit exceeded: 8")]
+fn test_render_self_reference() {
+    let data = json!({ "a": "{{ b | render }}", "b": "{{ a | render }}" });
+    let options = FillOptions {
+        recursion_limit: 8,
+        ..FillOptions::default()
+    };
+    fill_with_options("{{ a | render }}", Some(&data), &options);
+}
@@ -0,0 +1,174 @@
+use serde_json::{json, Value};
+use template_filling::{
+    try_fill_with_mode, FillOptions, LoopStat, MissingPlaceholder, RenderMode, RenderReport,
+    Template,
+};
+
+const REPORT: RenderMode = RenderMode {
+    block: None,
+    recover: false,
+    report: true,
+    source_map: false,
+};
+
+fn fill_with_report(
+    template: &str,
+    data: Option<&Value>,
+    options: &FillOptions,
+) -> (String, RenderReport) {
+    let output = try_fill_with_mode(template, data, options, &REPORT).unwrap();
+    (output.output, output.report.unwrap())
+}
+
+#[test]
+fn test_report_missing_placeholders() {
+    let (filled, report) = fill_with_report(
+        "{{ name }}: {{ comment }}\n{% for c in columns %}{{ c.type }} {% endfor %}",
+        Some(&json!({ "name": "user", "columns": [{ "type": "int" }, {}] })),
+        &FillOptions::default(),
+    );
+    assert_eq!(
+        filled,
+        "user: {{comment: Not found}}\nint {{c.type: Not found}} "
+    );
+    assert_eq!(
+        report.missing_placeholders,
+        vec![
+            MissingPlaceholder {
+                placeholder: String::from("comment"),
+                count: 1
+            },
+            MissingPlaceholder {
+                placeholder: String::from("c.type"),
+                count: 1
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_report_unused_keys() {
+    let template = r#"{% with table %}CREATE TABLE {{ name }} (
+{% for column in columns %}{{ column.name }}{% if column.nullable %} NULL{% endif %}
+{% endfor %});{% endwith %}
+{% set kind %}table{% endset %}{{ kind }}"#;
+    let data = json!({
+        "table": {
+            "name": "user",
+            "comment": "users",
+            "columns": [
+                { "name": "id", "type": "int", "nullable": false },
+                { "name": "email", "type": "text" }
+            ]
+        },
+        "version": 1
+    });
+    let (_, report) = fill_with_report(template, Some(&data), &FillOptions::default());
+    assert_eq!(
+        report.unused_keys,
+        vec!["table.columns[].type", "table.comment", "version"]
+    );
+}
+
+#[test]
+fn test_report_unused_keys_in_macro() {
+    let template = r#"{% macro column(c, suffix="") %}{{ c.name }}{{ suffix }}{% endmacro %}
+{% for item in columns %}{{ column(item) }}{% endfor %}"#;
+    let data = json!({ "columns": [{ "name": "id", "type": "int" }], "tags": ["a"] });
+    let (_, report) = fill_with_report(template, Some(&data), &FillOptions::default());
+    assert_eq!(report.unused_keys, vec!["columns[].type", "tags[]"]);
+}
+
+#[test]
+fn test_report_loops() {
+    let template = "{% for t in tables %}\n  {% for c in t.columns %}{{ c }}{% endfor %}\n{% endfor %}\n{% for x in missing %}{% endfor %}";
+    let data = json!({ "tables": [{ "columns": ["a", "b"] }, { "columns": ["c"] }] });
+    let report = Template::new(template)
+        .try_render_with_mode(Some(&data), &REPORT)
+        .unwrap()
+        .report
+        .unwrap();
+    assert!(report.unused_keys.is_empty());
+    assert_eq!(
+        report.loops,
+        vec![
+            LoopStat {
+                expression: String::from("t in tables"),
+                template: None,
+                line: 1,
+                column: 1,
+                runs: 1,
+                iterations: 2
+            },
+            LoopStat {
+                expression: String::from("c in t.columns"),
+                template: None,
+                line: 2,
+                column: 3,
+                runs: 2,
+                iterations: 3
+            },
+            LoopStat {
+                expression: String::from("x in missing"),
+                template: None,
+                line: 4,
+                column: 1,
+                runs: 1,
+                iterations: 0
+            },
+        ]
+    );
+    assert_eq!(
+        report.to_string(),
+        "Missing placeholders:
+  (none)
+Unused data keys:
+  (none)
+Loops:
+  t in tables at 1:1, 1 runs, 2 iterations
+  c in t.columns at 2:3, 2 runs, 3 iterations
+  x in missing at 4:1, 1 runs, 0 iterations"
+    );
+}
+
+#[test]
+fn test_report_recursive_loop() {
+    let template =
+        "{% for node in tree recursive %}{{ node.name }}{{ loop(node.children) }}{% endfor %}";
+    let data = json!({ "tree": [
+        { "name": "a", "children": [{ "name": "b", "children": [] }] },
+        { "name": "c" }
+    ] });
+    let (filled, report) = fill_with_report(template, Some(&data), &FillOptions::default());
+    assert_eq!(filled, "abc");
+    assert_eq!(report.unused_keys, Vec::<String>::new());
+    assert_eq!((report.loops[0].runs, report.loops[0].iterations), (4, 3));
+}
+
+#[test]
+fn test_report_with_recovery_and_source_map() {
+    let template = "{% block row %}{{ name }} {{ type }}{% endblock %}";
+    let mode = RenderMode {
+        block: Some("row".to_owned()),
+        recover: true,
+        report: true,
+        source_map: true,
+    };
+    let output = try_fill_with_mode(
+        template,
+        Some(&json!({ "name": "id" })),
+        &FillOptions::default(),
+        &mode,
+    )
+    .unwrap();
+    assert_eq!(output.block.as_deref(), Some("id {{type: Not found}}"));
+    assert_eq!(output.diagnostics.len(), 1);
+    assert_eq!(
+        output.report.unwrap().missing_placeholders,
+        vec![MissingPlaceholder {
+            placeholder: String::from("type"),
+            count: 1
+        }]
+    );
+    assert_eq!(output.source_map.unwrap().mappings.len(), 3);
+}
@@ -0,0 +1,173 @@
+use std::rc::Rc;
+
+use serde_json::{json, Value};
+use template_filling::{
+    ast::Span, try_fill_with_mode, FillOptions, IndentStyle, LineEnding, MemoryLoader, RenderMode,
+    SourceMap, SourceMapping, Template, TrailingNewline,
+};
+
+const SOURCE_MAP: RenderMode = RenderMode {
+    block: None,
+    recover: false,
+    report: false,
+    source_map: true,
+};
+
+fn fill_with_source_map(
+    template: &str,
+    data: Option<&Value>,
+    options: &FillOptions,
+) -> (String, SourceMap) {
+    let output = try_fill_with_mode(template, data, options, &SOURCE_MAP).unwrap();
+    (output.output, output.source_map.unwrap())
+}
+
+const TEMPLATE: &str = r#"CREATE TABLE {{ name }} (
+    {% for column in columns %}
+    {$ join_with = ,\n $}
+    {{ column.name }} {{ column.type }}
+    {% endfor %}
+);"#;
+
+#[test]
+fn test_source_map_output_is_unchanged() {
+    let data = json!({ "name": "user", "columns": [
+        { "name": "id", "type": "INT" },
+        { "name": "email", "type": "TEXT" }
+    ] });
+    let options = FillOptions::default();
+    let (filled, _) = fill_with_source_map(TEMPLATE, Some(&data), &options);
+    assert_eq!(
+        filled,
+        template_filling::fill_with_options(TEMPLATE, Some(&data), &options)
+    );
+    assert_eq!(
+        filled,
+        "CREATE TABLE user (\n    id INT,\n    email TEXT\n);"
+    );
+}
+
+#[test]
+fn test_source_map_loop_iterations() {
+    let data = json!({ "name": "user", "columns": [
+        { "name": "id", "type": "INT" },
+        { "name": "email", "type": "TEXT" }
+    ] });
+    let (filled, source_map) = fill_with_source_map(TEMPLATE, Some(&data), &FillOptions::default());
+    let email = filled.find("email").unwrap();
+    assert_eq!(
+        source_map.origin(email),
+        Some(&SourceMapping {
+            output: Span::new(email, email + "email".len()),
+            template: None,
+            line: 4,
+            column: 5,
+            iterations: vec![1],
+        })
+    );
+    let origins: Vec<Option<(usize, Vec<usize>)>> = source_map
+        .line_origins(&filled)
+        .into_iter()
+        .map(|origin| origin.map(|mapping| (mapping.line, mapping.iterations.clone())))
+        .collect();
+    assert_eq!(
+        origins,
+        vec![
+            Some((1, vec![])),
+            Some((4, vec![0])),
+            Some((4, vec![1])),
+            Some((6, vec![])),
+        ]
+    );
+}
+
+#[test]
+fn test_source_map_annotate() {
+    let data = json!({ "name": "user", "columns": [{ "name": "id", "type": "INT" }] });
+    let output = Template::new(TEMPLATE)
+        .try_render_with_mode(Some(&data), &SOURCE_MAP)
+        .unwrap();
+    let (filled, source_map) = (output.output, output.source_map.unwrap());
+    assert_eq!(
+        source_map.annotate(&filled, "ddl.tmpl"),
+        "ddl.tmpl:1    | CREATE TABLE user (\nddl.tmpl:4[0] |     id INT\nddl.tmpl:6    | );"
+    );
+}
+
+#[test]
+fn test_source_map_include_and_multi_line_value() {
+    let mut loader = MemoryLoader::new();
+    loader.insert("column.tmpl", "-- column\n{{ comment }}");
+    let options = FillOptions {
+        loader: Some(Rc::new(loader)),
+        ..FillOptions::default()
+    };
+    let (filled, source_map) = fill_with_source_map(
+        "BEGIN;\n{% include \"column.tmpl\" %}\n\nCOMMIT;",
+        Some(&json!({ "comment": "a\nb" })),
+        &options,
+    );
+    assert_eq!(filled, "BEGIN;\n-- column\na\nb\n\nCOMMIT;");
+    assert_eq!(
+        source_map.annotate(&filled, "main.tmpl"),
+        "main.tmpl:1   | BEGIN;
+column.tmpl:1 | -- column
+column.tmpl:2 | a
+column.tmpl:2 | b
+              | \n\
+main.tmpl:4   | COMMIT;"
+    );
+}
+
+#[test]
+fn test_source_map_is_not_in_variables() {
+    let (filled, source_map) = fill_with_source_map(
+        "{% set kind %}table{% endset %}{% if kind == \"table\" %}ok{% endif %}",
+        None,
+        &FillOptions::default(),
+    );
+    assert_eq!(filled, "ok");
+    assert_eq!(source_map.mappings.len(), 1);
+    assert_eq!(source_map.mappings[0].output, Span::new(0, 2));
+}
+
+#[test]
+fn test_source_map_keeps_private_use_chars() {
+    let data = json!({ "value": "\u{E000}0\u{E001}" });
+    let template = "{% set v %}{{ value }}{% endset %}[{{ v }}]";
+    let (filled, source_map) = fill_with_source_map(template, Some(&data), &FillOptions::default());
+    assert_eq!(filled, "[\u{E000}0\u{E001}]");
+    assert_eq!(source_map.mappings.len(), 3);
+    assert_eq!(
+        template_filling::fill_with_options(template, Some(&data), &FillOptions::default()),
+        filled
+    );
+}
+
+#[test]
+fn test_source_map_with_formatted_output() {
+    let data = json!({ "name": "user", "columns": [
+        { "name": "id", "type": "INT" },
+        { "name": "email", "type": "TEXT" }
+    ] });
+    let options = FillOptions {
+        line_ending: LineEnding::Crlf,
+        indent_style: IndentStyle::Tabs,
+        trailing_newline: TrailingNewline::Ensure,
+        ..FillOptions::default()
+    };
+    let (filled, source_map) = fill_with_source_map(TEMPLATE, Some(&data), &options);
+    assert_eq!(
+        filled,
+        "CREATE TABLE user (\r\n\tid INT,\r\n\temail TEXT\r\n);\r\n"
+    );
+    let email = filled.find("email").unwrap();
+    assert_eq!(
+        source_map.origin(email).map(|mapping| mapping.output),
+        Some(Span::new(email, email + "email".len()))
+    );
+    assert_eq!(
+        source_map.annotate(&filled, "ddl.tmpl"),
+        "ddl.tmpl:1    | CREATE TABLE user (\r\nddl.tmpl:4[0] | \tid INT,\r\nddl.tmpl:4[1] | \temail TEXT\r\nddl.tmpl:6    | );\r\n"
+    );
+}
@@ -0,0 +1,117 @@
+use serde_json::json;
+use template_filling::{fill, fill_with_options, FillOptions, Syntax};
+
+#[test]
+fn test_preset() {
+    let data = json!({ "name": "abc", "arrays": [1, 2, 3] });
+    let options = FillOptions {
+        syntax: Syntax::preset("bracket").unwrap(),
+        ..Default::default()
+    };
+    assert_eq!(
+        fill_with_options(
+            "[% for i in arrays %][$ join_with = , $][[i]][% endfor %] {{name}}",
+            Some(&data),
+            &options
+        ),
+        "1,2,3 {{name}}"
+    );
+    assert_eq!(
+        fill_with_options("[# [[name]] #][[ name ]]", Some(&data), &options),
+        " [[name]] abc"
+    );
+}
+
+#[test]
+fn test_spec() {
+    let data = json!({ "name": "abc" });
+    let syntax: Syntax = "logical=<% %> placeholder=<< >>".parse().unwrap();
+    let options = FillOptions {
+        syntax,
+        ..Default::default()
+    };
+    assert_eq!(
+        fill_with_options(
+            r#"<% if name == "abc" %><< name >><% endif %>{{ name }}"#,
+            Some(&data),
+            &options
+        ),
+        "abc{{ name }}"
+    );
+    assert!("unknown".parse::<Syntax>().is_err());
+    assert!("logical=<%".parse::<Syntax>().is_err());
+    assert!("placeholder={% %}".parse::<Syntax>().is_err());
+}
+
+#[test]
+fn test_spec_prefix_delimiters() {
+    assert_eq!(
+        "logical=<% %> placeholder=<%= =%>".parse::<Syntax>(),
+        Err("Syntax delimiter is a prefix of another: <% and <%=".to_owned())
+    );
+    assert_eq!(
+        "logical=<% %> placeholder=<< %>>".parse::<Syntax>(),
+        Err("Syntax delimiter is a prefix of another: %> and %>>".to_owned())
+    );
+    // The comment head extends the logical head with `#`
+    assert!("logical=<% %> comment=<%# #%>".parse::<Syntax>().is_ok());
+    for name in ["default", "bracket", "angle"] {
+        assert!(name.parse::<Syntax>().is_ok());
+    }
+}
+
+#[test]
+fn test_directive() {
+    let data = json!({ "name": "abc" });
+    assert_eq!(
+        fill("#!syntax bracket\n[[ name ]] {{ name }}", Some(&data)),
+        "abc {{ name }}"
+    );
+    assert_eq!(
+        fill(
+            "#!syntax default placeholder=${ }\r\n${name} {{ name }}",
+            Some(&data)
+        ),
+        "abc {{ name }}"
+    );
+}
+
+#[test]
+fn test_escape() {
+    let data = json!({ "name": "abc", "dir": "Users" });
+    let options = FillOptions {
+        escape_symbols: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        fill_with_options(r"\{{ name }} {{ name }}", Some(&data), &options),
+        "{{ name }} abc"
+    );
+    assert_eq!(fill_with_options(r"\{% if %}", None, &options), "{% if %}");
+    assert_eq!(fill_with_options(r"\\ \{", None, &options), r"\\ \{");
+    // The escaped backslash is filled as a backslash, and the symbol after it is filled
+    assert_eq!(
+        fill_with_options(r"C:\\{{ dir }}", Some(&data), &options),
+        r"C:\Users"
+    );
+}
+
+#[test]
+fn test_backslash_before_symbol() {
+    let data = json!({ "dir": "Users" });
+    // The backslash is text if the escape is off, like the Windows path
+    assert_eq!(
+        fill(r"C:\{{ dir }}\{{ dir }}", Some(&data)),
+        r"C:\Users\Users"
+    );
+    assert_eq!(fill(r"\\{{ dir }}", Some(&data)), r"\\Users");
+}
+
+#[test]
+fn test_tail_in_string() {
+    let data = json!({ "name": "%}" });
+    assert_eq!(
+        fill(r#"{% if name == "%}" %}Pass{% endif %}"#, Some(&data)),
+        "Pass"
+    );
+}
@@ -78,3 +78,34 @@ fn test_set_env() {
         "Pass"
     );
 }
+
+#[test]
+fn test_unquoted_value() {
+    let data = json!({ "attr_str": "abc" });
+    // The defined variable is filled with its value
+    assert_eq!(
+        fill(r#"{$ sep = attr_str $}[{{ sep }}]"#, Some(&data)),
+        "[abc]"
+    );
+    // The text which is not an expression, or the name which is not defined, is used as it is,
+    // like the separator of 'join_with'
+    assert_eq!(fill(r#"{$ sep = , $}[{{ sep }}]"#, None), "[,]");
+    assert_eq!(fill(r#"{$ sep = \n $}[{{ sep }}]"#, None), "[\n]");
+    assert_eq!(
+        fill(r#"{$ sep = 分隔 $}[{{ sep }}]"#, Some(&data)),
+        "[分隔]"
+    );
+}
+
+#[test]
+fn test_env_does_not_hide_data() {
+    let data = json!({ "name": { "first": "F" } });
+    // The key which is not found in the env is looked up in the data
+    assert_eq!(
+        fill(
+            r#"{$ name = abc $}{{ name }} {{ name.first }}"#,
+            Some(&data)
+        ),
+        "abc F"
+    );
+}
@@ -0,0 +1,94 @@
+use std::rc::Rc;
+
+use serde_json::json;
+use template_filling::{fill, try_fill, FillError, FillOptions, MemoryLoader, Template};
+
+#[test]
+fn test_assert() {
+    let template = r#"{% for table in tables %}
+{$ join_with = \n $}
+{% assert table.primary_key is defined, "table {{ table.name }} has no PK" %}
+ALTER TABLE {{ table.name }} ADD PRIMARY KEY ({{ table.primary_key }});
+{% endfor %}"#;
+    let data = json!({ "tables": [{ "name": "user", "primary_key": "id" }] });
+    assert_eq!(
+        try_fill(template, Some(&data), &FillOptions::default()).unwrap(),
+        "ALTER TABLE user ADD PRIMARY KEY (id);"
+    );
+    let data = json!({ "tables": [
+        { "name": "user", "primary_key": "id" },
+        { "name": "log" }
+    ] });
+    assert_eq!(
+        try_fill(template, Some(&data), &FillOptions::default()),
+        Err(FillError {
+            message: "table log has no PK".to_owned(),
+            template: None,
+            line: 3,
+            column: 1,
+        })
+    );
+}
+
+#[test]
+fn test_assert_condition() {
+    let data = json!({ "kind": "table", "strict": false });
+    let options = FillOptions::default();
+    assert!(try_fill("{% assert kind == \"table\" %}", Some(&data), &options).is_ok());
+    assert!(try_fill("{% assert kind != \"view\" %}", Some(&data), &options).is_ok());
+    assert!(try_fill("{% assert comment is not defined %}", Some(&data), &options).is_ok());
+    assert_eq!(
+        try_fill("ok\n  {% assert strict %}", Some(&data), &options)
+            .unwrap_err()
+            .to_string(),
+        "Assertion failed: strict, at 2:3"
+    );
+}
+
+#[test]
+fn test_error() {
+    let template = r#"{% match kind %}
+{% case "table" %}
+CREATE TABLE t ();
+{% default %}
+    {% error "unsupported kind: {{ kind }}" %}
+{% endmatch %}"#;
+    let template = Template::new(template);
+    assert_eq!(
+        template
+            .try_render(Some(&json!({ "kind": "table" })))
+            .unwrap(),
+        "CREATE TABLE t ();"
+    );
+    let error = template
+        .try_render(Some(&json!({ "kind": "view" })))
+        .unwrap_err();
+    assert_eq!(error.message, "unsupported kind: view");
+    assert_eq!((error.line, error.column), (5, 5));
+}
+
+#[test]
+fn test_error_in_include() {
+    let mut loader = MemoryLoader::new();
+    loader.insert("column.tmpl", "{{ name }}\n{% error message %}");
+    let options = FillOptions {
+        loader: Some(Rc::new(loader)),
+        ..FillOptions::default()
+    };
+    let data = json!({ "name": "id", "message": "no type" });
+    assert_eq!(
+        try_fill("({% include \"column.tmpl\" %})", Some(&data), &options)
+            .unwrap_err()
+            .to_string(),
+        "no type, at column.tmpl:2:1"
+    );
+}
+
+#[test]
+#[should_panic(expected = "table log has no PK, at 1:1")]
+fn test_assert_panic() {
+    fill(
+        "{% assert primary_key is defined, \"table {{ name }} has no PK\" %}",
+        Some(&json!({ "name": "log" })),
+    );
+}
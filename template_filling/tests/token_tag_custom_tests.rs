@@ -0,0 +1,155 @@
+use std::rc::Rc;
+
+use serde_json::{json, Value};
+use template_filling::{fill_with_options, CustomTag, FillOptions, TagContext};
+
+/// Inline tag, like `{% license "MIT" %}`
+#[derive(Debug)]
+struct License;
+
+impl CustomTag for License {
+    fn name(&self) -> &str {
+        "license"
+    }
+
+    fn parse(&self, body: &str) -> Result<Value, String> {
+        match body
+            .strip_prefix('"')
+            .and_then(|body| body.strip_suffix('"'))
+        {
+            Some(license) => Ok(Value::String(license.to_owned())),
+            None => Err(String::from("license must be a string")),
+        }
+    }
+
+    fn render(&self, args: &Value, ctx: &mut TagContext) -> String {
+        ctx.fill_content(&format!(
+            "-- License: {}\n-- Author: {{{{ author }}}}",
+            args.as_str().unwrap()
+        ))
+    }
+}
+
+/// Block tag, like `{% region name %}...{% endregion %}`
+#[derive(Debug)]
+struct Region;
+
+impl CustomTag for Region {
+    fn name(&self) -> &str {
+        "region"
+    }
+
+    fn has_end_tag(&self) -> bool {
+        true
+    }
+
+    fn render(&self, args: &Value, ctx: &mut TagContext) -> String {
+        ctx.set("$region", args.clone());
+        let begin = ctx.fill_content("-- region {{ $region }}");
+        let body = ctx.fill_body();
+        let end = ctx.fill_content("-- endregion");
+        format!("{}\n{}\n{}", begin, body, end)
+    }
+}
+
+/// Block tag which fills its body repeatedly, like `{% repeat 3 %}...{% endrepeat %}`
+#[derive(Debug)]
+struct Repeat;
+
+impl CustomTag for Repeat {
+    fn name(&self) -> &str {
+        "repeat"
+    }
+
+    fn has_end_tag(&self) -> bool {
+        true
+    }
+
+    fn parse(&self, body: &str) -> Result<Value, String> {
+        serde_json::from_str(body).map_err(|e| e.to_string())
+    }
+
+    fn render(&self, args: &Value, ctx: &mut TagContext) -> String {
+        let times = match args {
+            Value::Number(times) => times.as_u64().unwrap(),
+            _ => ctx.value(args.as_str().unwrap()).as_u64().unwrap(),
+        };
+        (0..times)
+            .map(|i| {
+                ctx.set("$i", json!(i));
+                ctx.fill_body()
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+fn options() -> FillOptions {
+    FillOptions {
+        custom_tags: vec![Rc::new(License), Rc::new(Region), Rc::new(Repeat)],
+        ..FillOptions::default()
+    }
+}
+
+#[test]
+fn test_custom_inline_tag() {
+    let data = json!({ "author": "Gin" });
+    assert_eq!(
+        fill_with_options(
+            "{% license \"MIT\" %}\nCREATE TABLE user ();",
+            Some(&data),
+            &options()
+        ),
+        "-- License: MIT\n-- Author: Gin\nCREATE TABLE user ();"
+    );
+}
+
+#[test]
+fn test_custom_block_tag() {
+    let data = json!({ "columns": ["id", "name"] });
+    assert_eq!(
+        fill_with_options(
+            r#"CREATE TABLE user (
+    {% region columns %}
+    {% for column in columns %}
+    {$ join_with = ,\n $}
+    {{ column }} TEXT
+    {% endfor %}
+    {% endregion %}
+);"#,
+            Some(&data),
+            &options()
+        ),
+        "CREATE TABLE user (\n    -- region columns\n    id TEXT,\n    name TEXT\n    -- endregion\n);"
+    );
+}
+
+#[test]
+fn test_custom_tag_with_data_context() {
+    let data = json!({ "times": 2 });
+    assert_eq!(
+        fill_with_options(
+            r#"{% repeat "times" %}
+INSERT INTO t VALUES ({{ $i }});
+{% endrepeat %}
+{% repeat 1 %}
+    {% license "MIT" %}
+{% endrepeat %}"#,
+            Some(&data),
+            &options()
+        ),
+        "INSERT INTO t VALUES (0);\nINSERT INTO t VALUES (1);\n-- License: MIT\n-- Author: {{author: Not found}}"
+    );
+}
+
+#[test]
+#[should_panic(expected = "Illegal expression: license, license must be a string")]
+fn test_custom_tag_parse_error() {
+    fill_with_options("{% license MIT %}", None, &options());
+}
+
+#[test]
+#[should_panic(expected = "Unsupported tag: license \"MIT\"")]
+fn test_custom_tag_not_registered() {
+    fill_with_options("{% license \"MIT\" %}", None, &FillOptions::default());
+}
@@ -0,0 +1,107 @@
+use std::rc::Rc;
+
+use serde_json::json;
+use template_filling::{fill, fill_with_options, FillOptions, MemoryLoader};
+
+const BASE: &str = r#"CREATE TABLE {{ name }} (
+    {% block columns %}
+    id INT
+    {% endblock %}
+) {% block options %}ENGINE=InnoDB{% endblock %};"#;
+
+fn memory_options(templates: &[(&str, &str)]) -> FillOptions {
+    let mut loader = MemoryLoader::new();
+    for (name, content) in templates {
+        loader.insert(*name, *content);
+    }
+    FillOptions {
+        loader: Some(Rc::new(loader)),
+        ..FillOptions::default()
+    }
+}
+
+#[test]
+fn test_block_without_extends() {
+    let data = json!({ "name": "user" });
+    assert_eq!(
+        fill(BASE, Some(&data)),
+        "CREATE TABLE user (\n    id INT\n) ENGINE=InnoDB;"
+    );
+}
+
+#[test]
+fn test_extends() {
+    let options = memory_options(&[("base.tmpl", BASE)]);
+    let data = json!({ "name": "user" });
+    assert_eq!(
+        fill_with_options(
+            r#"{% extends "base.tmpl" %}
+Ignored text
+{% block columns %}
+id BIGINT,
+name TEXT
+{% endblock %}"#,
+            Some(&data),
+            &options
+        ),
+        "CREATE TABLE user (\n    id BIGINT,\n    name TEXT\n) ENGINE=InnoDB;"
+    );
+}
+
+#[test]
+fn test_extends_super() {
+    let options = memory_options(&[("base.tmpl", BASE)]);
+    let data = json!({ "name": "user", "columns": ["name", "age"] });
+    assert_eq!(
+        fill_with_options(
+            r#"{% extends "base.tmpl" %}
+{% block columns %}
+    {{ super() }},
+    {% for column in columns %}
+    {$ join_with = ,\n $}
+    {{ column }} TEXT
+    {% endfor %}
+{% endblock %}
+{% block options %}{{ super() }} COMMENT='{{ name }}'{% endblock %}"#,
+            Some(&data),
+            &options
+        ),
+        "CREATE TABLE user (\n    id INT,\n    name TEXT,\n    age TEXT\n) ENGINE=InnoDB COMMENT='user';"
+    );
+}
+
+#[test]
+fn test_extends_multi_level() {
+    let options = memory_options(&[
+        ("base.tmpl", BASE),
+        (
+            "audit.tmpl",
+            "{% extends \"base.tmpl\" %}\n{% block columns %}\n{{ super() }},\ncreated_at DATETIME\n{% endblock %}",
+        ),
+    ]);
+    let data = json!({ "name": "user" });
+    assert_eq!(
+        fill_with_options(
+            "{% extends \"audit.tmpl\" %}\n{% block options %}{% endblock %}",
+            Some(&data),
+            &options
+        ),
+        "CREATE TABLE user (\n    id INT,\n    created_at DATETIME\n) ;"
+    );
+}
+
+#[test]
+#[should_panic(expected = "Extends cycle: a.tmpl -> b.tmpl -> a.tmpl")]
+fn test_extends_cycle() {
+    let options = memory_options(&[
+        ("a.tmpl", "{% extends \"b.tmpl\" %}"),
+        ("b.tmpl", "{% extends \"a.tmpl\" %}"),
+    ]);
+    fill_with_options("{% extends \"a.tmpl\" %}", None, &options);
+}
+
+#[test]
+#[should_panic(expected = "super() is only available in block")]
+fn test_super_out_of_block() {
+    fill("{{ super() }}", None);
+}
@@ -0,0 +1,94 @@
+use serde_json::json;
+use template_filling::{
+    fill_files, fill_with_options, try_fill_files, try_fill_with_mode, FillError, FillOptions,
+    RenderMode, Template, TrailingNewline,
+};
+
+const TABLE_TEMPLATE: &str = r#"{% file "{{ name }}.sql" %}
+CREATE TABLE {{ name }} (
+    id INT
+);
+{% endfile %}
+{% file "src/{{ name }}.rs" %}
+    pub struct {{ name }} {
+        pub id: i32,
+    }
+{% endfile %}"#;
+
+#[test]
+fn test_file() {
+    let data = json!({ "name": "user" });
+    assert_eq!(
+        Template::new(TABLE_TEMPLATE).render_files(Some(&data)),
+        vec![
+            (
+                "user.sql".to_owned(),
+                "CREATE TABLE user (\n    id INT\n);".to_owned()
+            ),
+            (
+                "src/user.rs".to_owned(),
+                "pub struct user {\n    pub id: i32,\n}".to_owned()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_file_in_for() {
+    let data = json!({ "tables": ["user", "role"], "path": "ddl" });
+    let (filled, files) = fill_files(
+        "-- generated\n{% for table in tables %}\n{% file \"{{ path }}/{{ table }}.sql\" %}\nDROP TABLE {{ table }};\n{% endfile %}\n{% endfor %}",
+        Some(&data),
+        &FillOptions::default(),
+    );
+    assert_eq!(filled, "-- generated\n");
+    assert_eq!(
+        files,
+        vec![
+            ("ddl/user.sql".to_owned(), "DROP TABLE user;".to_owned()),
+            ("ddl/role.sql".to_owned(), "DROP TABLE role;".to_owned()),
+        ]
+    );
+}
+
+#[test]
+fn test_file_with_options() {
+    let data = json!({ "name": "user", "file_name": "user.txt" });
+    let options = FillOptions {
+        trailing_newline: TrailingNewline::Ensure,
+        ..FillOptions::default()
+    };
+    let (_, files) = fill_files(
+        "{% file file_name %}{{ name }}{% endfile %}",
+        Some(&data),
+        &options,
+    );
+    assert_eq!(files, vec![("user.txt".to_owned(), "user\n".to_owned())]);
+    // The files are dropped in filling without files
+    assert_eq!(fill_with_options(TABLE_TEMPLATE, Some(&data), &options), "");
+}
+
+#[test]
+fn test_file_path_placeholder_not_found() {
+    let template = "-- generated\n{% file \"{{ nope }}.sql\" %}DROP TABLE user;{% endfile %}";
+    assert_eq!(
+        try_fill_files(template, None, &FillOptions::default()),
+        Err(FillError {
+            message: "File path placeholder not found: {{ nope }}.sql".to_owned(),
+            template: None,
+            line: 2,
+            column: 1,
+        })
+    );
+    // The file is not emitted in the recovery mode
+    let mode = RenderMode {
+        recover: true,
+        ..RenderMode::default()
+    };
+    let output = try_fill_with_mode(template, None, &FillOptions::default(), &mode).unwrap();
+    assert!(output.files.is_empty());
+    assert_eq!(
+        output.diagnostics[1].message,
+        "File path placeholder not found: {{ nope }}.sql"
+    );
+}
@@ -0,0 +1,79 @@
+use serde_json::json;
+use template_filling::{fill, fill_with_options, FillOptions};
+
+#[test]
+fn test_recursive_for() {
+    let data = json!({ "menus": [
+        { "name": "System", "group": true, "children": [
+            { "name": "User", "group": true, "children": [
+                { "name": "Role" }
+            ] },
+            { "name": "Log" }
+        ] },
+        { "name": "Help" }
+    ] });
+    assert_eq!(
+        fill(
+            r#"<ul>
+    {% for menu in menus recursive %}
+    {$ join_with = \n $}
+    <li>{{ menu.name }} ({{ $depth }})</li>
+    {% if menu.group %}
+    <ul>
+        {{ loop(menu.children) }}
+    </ul>
+    {% endif %}
+    {% endfor %}
+</ul>"#,
+            Some(&data)
+        ),
+        r#"<ul>
+    <li>System (0)</li>
+    <ul>
+        <li>User (1)</li>
+        <ul>
+            <li>Role (2)</li>
+        </ul>
+        <li>Log (1)</li>
+    </ul>
+    <li>Help (0)</li>
+</ul>"#
+    );
+}
+
+#[test]
+fn test_recursive_for_without_children() {
+    let data = json!({ "nodes": [{ "name": "a" }, { "name": "b" }] });
+    assert_eq!(
+        fill(
+            "{% for node in nodes recursive %}{{ node.name }}{{ loop(node.children) }}{% endfor %}",
+            Some(&data)
+        ),
+        "ab"
+    );
+}
+
+#[test]
+#[should_panic(expected = "Recursion limit exceeded: 2")]
+fn test_recursion_limit() {
+    let data = json!({ "nodes": [{ "children": [{ "children": [{ "children": [] }] }] }] });
+    let options = FillOptions {
+        recursion_limit: 2,
+        ..FillOptions::default()
+    };
+    fill_with_options(
+        "{% for node in nodes recursive %}{{ loop(node.children) }}{% endfor %}",
+        Some(&data),
+        &options,
+    );
+}
+
+#[test]
+#[should_panic(expected = "loop() is only available in recursive for")]
+fn test_loop_out_of_recursive_for() {
+    let data = json!({ "nodes": [1] });
+    fill(
+        "{% for node in nodes %}{{ loop(node) }}{% endfor %}",
+        Some(&data),
+    );
+}
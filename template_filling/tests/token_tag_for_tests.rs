@@ -106,3 +106,25 @@ fn test_join_with() {
         "1分隔2分隔3"
     );
 }
+
+#[test]
+fn test_key_value() {
+    let data = json!({
+        "user": { "name": "Leung", "age": 18 },
+        "arrays": [1, 2]
+    });
+    assert_eq!(
+        fill(
+            r#"{% for key, value in user %}{$ join_with = , $}{{ $index }}:{{ key }}={{ value }}{% endfor %}"#,
+            Some(&data)
+        ),
+        "0:age=18,1:name=Leung"
+    );
+    assert_eq!(
+        fill(
+            r#"{%for k,v in user%}{{ k }}{%endfor%}|{% for k, v in arrays %}{{ k }}{% endfor %}"#,
+            Some(&data)
+        ),
+        "agename|"
+    );
+}
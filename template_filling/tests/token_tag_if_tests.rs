@@ -1,5 +1,5 @@
 use serde_json::json;
-use template_filling::fill;
+use template_filling::{fill, try_fill, FillError, FillOptions};
 
 #[test]
 fn test_space() {
@@ -122,3 +122,25 @@ fn test_unequal() {
         ""
     );
 }
+
+#[test]
+fn test_unbalanced_tags() {
+    assert_eq!(
+        try_fill("a\n{% endif %}", None, &FillOptions::default()),
+        Err(FillError {
+            message: "Missing head tag of endif".to_owned(),
+            template: None,
+            line: 2,
+            column: 1,
+        })
+    );
+    assert_eq!(
+        try_fill("a\n  {% if true %}b", None, &FillOptions::default()),
+        Err(FillError {
+            message: "Unclosed tag if, missing endif".to_owned(),
+            template: None,
+            line: 2,
+            column: 3,
+        })
+    );
+}
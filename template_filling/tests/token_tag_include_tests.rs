@@ -0,0 +1,173 @@
+use std::{env, fs, rc::Rc};
+
+use serde_json::json;
+use template_filling::{
+    fill_with_options, try_fill, FileSystemLoader, FillError, FillOptions, MemoryLoader,
+    TemplateLoader,
+};
+
+fn memory_options(templates: &[(&str, &str)]) -> FillOptions {
+    let mut loader = MemoryLoader::new();
+    for (name, content) in templates {
+        loader.insert(*name, *content);
+    }
+    FillOptions {
+        loader: Some(Rc::new(loader)),
+        ..FillOptions::default()
+    }
+}
+
+#[test]
+fn test_include() {
+    let options = memory_options(&[("header.tmpl", "-- Table: {{ name }}\n-- Author: me\n")]);
+    let data = json!({ "name": "user" });
+    assert_eq!(
+        fill_with_options(
+            "{% include \"header.tmpl\" %}\nCREATE TABLE {{ name }};",
+            Some(&data),
+            &options
+        ),
+        "-- Table: user\n-- Author: me\nCREATE TABLE user;"
+    );
+    // Template name from variable
+    let data = json!({ "name": "user", "partial": "header.tmpl" });
+    assert_eq!(
+        fill_with_options("{% include partial %}", Some(&data), &options),
+        "-- Table: user\n-- Author: me"
+    );
+}
+
+#[test]
+fn test_include_with_scope_and_indent() {
+    let options = memory_options(&[("column.tmpl", "{{ column.name }} {{ column.type }},")]);
+    let data = json!({ "columns": [
+        { "name": "id", "type": "INT" },
+        { "name": "name", "type": "TEXT" }
+    ] });
+    assert_eq!(
+        fill_with_options(
+            "CREATE TABLE t (\n    {% for column in columns %}\n    {$ join_with = \\n $}\n    {% include \"column.tmpl\" %}\n    {% endfor %}\n);",
+            Some(&data),
+            &options
+        ),
+        "CREATE TABLE t (\n    id INT,\n    name TEXT,\n);"
+    );
+}
+
+#[test]
+fn test_include_with_context() {
+    let options = memory_options(&[("audit.tmpl", "created_by {{ user }}\nupdated_by {{ user }}")]);
+    let data = json!({ "user": "root", "audit": { "user": "admin" } });
+    assert_eq!(
+        fill_with_options(
+            "  {% include \"audit.tmpl\" with audit %}",
+            Some(&data),
+            &options
+        ),
+        "  created_by admin\n  updated_by admin"
+    );
+}
+
+#[test]
+#[should_panic(expected = "Include cycle: a.tmpl -> b.tmpl -> a.tmpl")]
+fn test_include_cycle() {
+    let options = memory_options(&[
+        ("a.tmpl", "{% include \"b.tmpl\" %}"),
+        ("b.tmpl", "{% include \"a.tmpl\" %}"),
+    ]);
+    fill_with_options("{% include \"a.tmpl\" %}", None, &options);
+}
+
+#[test]
+#[should_panic(expected = "No template loader to load")]
+fn test_include_without_loader() {
+    fill_with_options("{% include \"a.tmpl\" %}", None, &FillOptions::default());
+}
+
+#[test]
+fn test_try_include_cycle() {
+    let options = memory_options(&[
+        ("a.tmpl", "{% include \"b.tmpl\" %}"),
+        ("b.tmpl", "-- b\n  {% include \"a.tmpl\" %}"),
+    ]);
+    assert_eq!(
+        try_fill("{% include \"a.tmpl\" %}", None, &options),
+        Err(FillError {
+            message: "Include cycle: a.tmpl -> b.tmpl -> a.tmpl".to_owned(),
+            template: Some("b.tmpl".to_owned()),
+            line: 2,
+            column: 3,
+        })
+    );
+}
+
+#[test]
+fn test_try_include_load_fail() {
+    let options = memory_options(&[]);
+    assert_eq!(
+        try_fill("-- header\n{% include \"a.tmpl\" %}", None, &options),
+        Err(FillError {
+            message: "Load template fail: a.tmpl, Template not found: a.tmpl".to_owned(),
+            template: None,
+            line: 2,
+            column: 1,
+        })
+    );
+}
+
+#[test]
+fn test_try_fill_without_loader() {
+    for template in [
+        "{% include \"a.tmpl\" %}",
+        "{% extends \"a.tmpl\" %}",
+        "{% import \"a.tmpl\" as a %}",
+    ] {
+        let error = try_fill(template, None, &FillOptions::default()).unwrap_err();
+        assert_eq!(error.message, "No template loader to load: a.tmpl");
+        assert_eq!((error.line, error.column), (1, 1));
+    }
+}
+
+#[test]
+fn test_try_include_unbalanced() {
+    let options = memory_options(&[("a.tmpl", "-- a\n{% endfor %}")]);
+    assert_eq!(
+        try_fill("{% include \"a.tmpl\" %}", None, &options),
+        Err(FillError {
+            message: "Missing head tag of endfor".to_owned(),
+            template: Some("a.tmpl".to_owned()),
+            line: 2,
+            column: 1,
+        })
+    );
+}
+
+#[test]
+fn test_file_system_loader() {
+    let root = env::temp_dir().join("template_filling_include_tests");
+    fs::create_dir_all(root.join("partials")).unwrap();
+    fs::write(root.join("partials/header.tmpl"), "-- {{ name }}\n").unwrap();
+    fs::write(root.join("secret.txt"), "secret").unwrap();
+
+    let loader = FileSystemLoader::new(root.join("partials"));
+    assert_eq!(loader.load("header.tmpl").unwrap(), "-- {{ name }}\n");
+    assert!(loader.load("../secret.txt").is_err());
+    assert!(loader
+        .load(root.join("secret.txt").to_str().unwrap())
+        .is_err());
+    assert!(loader.load("missing.tmpl").is_err());
+
+    let options = FillOptions {
+        loader: Some(Rc::new(loader)),
+        ..FillOptions::default()
+    };
+    let data = json!({ "name": "user" });
+    assert_eq!(
+        fill_with_options(
+            "{% include \"header.tmpl\" %}\nSELECT 1;",
+            Some(&data),
+            &options
+        ),
+        "-- user\nSELECT 1;"
+    );
+}
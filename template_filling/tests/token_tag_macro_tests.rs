@@ -0,0 +1,157 @@
+use std::rc::Rc;
+
+use serde_json::json;
+use template_filling::{fill, fill_with_options, try_fill, FillOptions, MemoryLoader};
+
+#[test]
+fn test_macro() {
+    let data = json!({ "table": "user" });
+    assert_eq!(
+        fill(
+            r#"{% macro column(name, type, nullable=true) %}{{ name }} {{ type }}{% if nullable == false %} NOT NULL{% endif %}{% endmacro %}
+CREATE TABLE {{ table }} (
+    {{ column("id", "INT", nullable=false) }},
+    {{ column("name", "TEXT") }}
+);"#,
+            Some(&data)
+        ),
+        "CREATE TABLE user (\n    id INT NOT NULL,\n    name TEXT\n);"
+    );
+}
+
+#[test]
+fn test_macro_with_variable_arguments() {
+    let data = json!({ "columns": [
+        { "name": "id", "type": "INT" },
+        { "name": "name", "type": "TEXT" }
+    ] });
+    assert_eq!(
+        fill(
+            r#"{% macro column(col) %}{{ col.name }} {{ col.type }}{% endmacro %}
+{% for col in columns %}
+{$ join_with = ,\n $}
+{{ column(col) }}
+{% endfor %}"#,
+            Some(&data)
+        ),
+        "id INT,\nname TEXT"
+    );
+}
+
+#[test]
+fn test_macro_scope_and_indent() {
+    let data = json!({ "name": "global" });
+    assert_eq!(
+        fill(
+            r#"{% macro getter(name) %}
+pub fn {{ name }}(&self) -> &str {
+    &self.{{ name }}
+}
+{% endmacro %}
+impl User {
+    {{ getter("email") }}
+}
+{{ name }}"#,
+            Some(&data)
+        ),
+        "impl User {\n    pub fn email(&self) -> &str {\n        &self.email\n    }\n}\nglobal"
+    );
+}
+
+#[test]
+fn test_import_macro() {
+    let mut loader = MemoryLoader::new();
+    loader.insert(
+        "macros.tmpl",
+        "{% macro pk(name) %}PRIMARY KEY ({{ name }}){% endmacro %}",
+    );
+    let options = FillOptions {
+        loader: Some(Rc::new(loader)),
+        ..FillOptions::default()
+    };
+    assert_eq!(
+        fill_with_options(
+            "{% import \"macros.tmpl\" %}\n{{ pk(\"id\") }}",
+            None,
+            &options
+        ),
+        "PRIMARY KEY (id)"
+    );
+    assert_eq!(
+        fill_with_options(
+            "{% import \"macros.tmpl\" as m %}\n{{ m.pk(\"id\") }}",
+            None,
+            &options
+        ),
+        "PRIMARY KEY (id)"
+    );
+}
+
+#[test]
+fn test_import_macro_calls_sibling() {
+    let mut loader = MemoryLoader::new();
+    loader.insert(
+        "macros.tmpl",
+        "{% macro inner(n) %}<{{ n }}>{% endmacro %}{% macro outer(n) %}({{ inner(n) }}){% endmacro %}",
+    );
+    let options = FillOptions {
+        loader: Some(Rc::new(loader)),
+        ..FillOptions::default()
+    };
+    assert_eq!(
+        fill_with_options(
+            "{% import \"macros.tmpl\" as l %}\n{{ l.outer(1) }}",
+            None,
+            &options
+        ),
+        "(<1>)"
+    );
+    // The siblings are only visible while the imported macro is filled
+    assert_eq!(
+        fill_with_options(
+            "{% import \"macros.tmpl\" as l %}\n{{ l.outer(1) }} {{ inner(2) }}",
+            None,
+            &options
+        ),
+        "(<1>) {{inner(2): Not found}}"
+    );
+}
+
+#[test]
+fn test_macro_recursion_limit() {
+    let options = FillOptions {
+        recursion_limit: 8,
+        ..FillOptions::default()
+    };
+    let error = try_fill(
+        "{% macro m(n) %}{{ m(n) }}{% endmacro %}{{ m(1) }}",
+        None,
+        &options,
+    )
+    .unwrap_err();
+    assert_eq!(error.message, "Recursion limit exceeded: 8");
+    // The default limit is reached before the stack overflows
+    assert!(try_fill(
+        "{% macro m(n) %}{{ m(n) }}{% endmacro %}{{ m(1) }}",
+        None,
+        &FillOptions::default(),
+    )
+    .is_err());
+}
+
+#[test]
+fn test_undefined_macro() {
+    assert_eq!(
+        fill("{{ missing(\"id\") }}", None),
+        "{{missing(\"id\"): Not found}}"
+    );
+}
+
+#[test]
+#[should_panic(expected = "Unknown macro argument: size")]
+fn test_macro_unknown_argument() {
+    fill(
+        "{% macro column(name) %}{{ name }}{% endmacro %}{{ column(\"id\", size=1) }}",
+        None,
+    );
+}
@@ -0,0 +1,79 @@
+use serde_json::json;
+use template_filling::fill;
+
+const TYPE_MAPPING: &str = r#"{% for col in columns %}
+{$ join_with = ,\n $}
+{{ col.name }}: {% match col.type %}
+{% case "int", "bigint" %}
+i64
+{% case "text", "varchar" %}
+String
+{% case true %}
+bool
+{% default %}
+Value
+{% endmatch %}
+{% endfor %}"#;
+
+#[test]
+fn test_match() {
+    let data = json!({ "columns": [
+        { "name": "id", "type": "bigint" },
+        { "name": "name", "type": "varchar" },
+        { "name": "flag", "type": true },
+        { "name": "meta", "type": "json" }
+    ] });
+    assert_eq!(
+        fill(TYPE_MAPPING, Some(&data)),
+        "id: i64,\nname: String,\nflag: bool,\nmeta: Value"
+    );
+}
+
+#[test]
+fn test_match_typed() {
+    let template =
+        r#"{% match code %}{% case 1 %}number{% case "1" %}string{% default %}other{% endmatch %}"#;
+    assert_eq!(fill(template, Some(&json!({ "code": 1 }))), "number");
+    assert_eq!(fill(template, Some(&json!({ "code": 1.0 }))), "number");
+    assert_eq!(fill(template, Some(&json!({ "code": "1" }))), "string");
+    assert_eq!(fill(template, Some(&json!({ "code": true }))), "other");
+    assert_eq!(fill(template, None), "other");
+}
+
+#[test]
+fn test_match_without_default() {
+    let data = json!({ "kind": "view" });
+    assert_eq!(
+        fill(
+            "begin\n{% match kind %}\n{% case \"table\" %}\nCREATE TABLE\n{% endmatch %}\nend",
+            Some(&data)
+        ),
+        "begin\nend"
+    );
+}
+
+#[test]
+fn test_match_indent() {
+    let data = json!({ "kind": "pk" });
+    assert_eq!(
+        fill(
+            r#"(
+    {% match kind %}
+    {% default %}
+        -- none
+    {% case "pk" %}
+        PRIMARY KEY (id),
+        CHECK (id > 0)
+    {% endmatch %}
+)"#,
+            Some(&data)
+        ),
+        "(\n    PRIMARY KEY (id),\n    CHECK (id > 0)\n)"
+    );
+}
+
+#[test]
+#[should_panic(expected = "Tag case and default must be in match")]
+fn test_case_out_of_match() {
+    fill("{% case 1 %}1", None);
+}
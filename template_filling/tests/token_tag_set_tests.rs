@@ -0,0 +1,66 @@
+use serde_json::json;
+use template_filling::fill;
+
+#[test]
+fn test_set() {
+    let data = json!({ "table": "user", "columns": ["id", "name", "age"] });
+    assert_eq!(
+        fill(
+            r#"{% set column_list %}{% for column in columns %}{$ join_with = , $}{{ column }}{% endfor %}{% endset %}
+INSERT INTO {{ table }} ({{ column_list }}) VALUES (?, ?, ?);
+SELECT {{ column_list }} FROM {{ table }};"#,
+            Some(&data)
+        ),
+        "INSERT INTO user (id,name,age) VALUES (?, ?, ?);\nSELECT id,name,age FROM user;"
+    );
+}
+
+#[test]
+fn test_set_multi_line_with_filter() {
+    let data = json!({ "columns": ["id", "name"] });
+    assert_eq!(
+        fill(
+            r#"    {% set select %}
+    SELECT
+        {% for column in columns %}
+        {$ join_with = ,\n $}
+        {{ column }}
+        {% endfor %}
+    FROM t
+    {% endset %}
+(
+    {{ select | indent }}
+)"#,
+            Some(&data)
+        ),
+        "(\n    SELECT\n        id,\n        name\n    FROM t\n)"
+    );
+}
+
+#[test]
+fn test_set_in_condition() {
+    let data = json!({ "kind": "pk" });
+    assert_eq!(
+        fill(
+            r#"{% set constraint %}{% if kind == "pk" %}PRIMARY KEY{% endif %}{% endset %}
+{% if constraint == "PRIMARY KEY" %}
+has primary key
+{% endif %}"#,
+            Some(&data)
+        ),
+        "has primary key"
+    );
+}
+
+#[test]
+fn test_set_scope() {
+    let data = json!({ "items": ["a", "b"] });
+    assert_eq!(
+        fill(
+            r#"{% for item in items %}{$ join_with = , $}{% set upper %}[{{ item }}]{% endset %}{{ upper }}{% endfor %}
+{{ upper }}"#,
+            Some(&data)
+        ),
+        "[a],[b]\n{{upper: Not found}}"
+    );
+}
@@ -0,0 +1,64 @@
+use serde_json::json;
+use template_filling::fill;
+
+#[test]
+fn test_with_binding() {
+    let data = json!({ "schema": { "table": { "name": "user", "primary_key": { "columns": ["id", "tenant_id"] } } } });
+    assert_eq!(
+        fill(
+            r#"{% with pk = schema.table.primary_key, name = schema.table.name %}
+ALTER TABLE {{ name }} ADD PRIMARY KEY ({% for column in pk.columns %}{$ join_with = , $}{{ column }}{% endfor %});
+{% endwith %}
+{{ pk }}"#,
+            Some(&data)
+        ),
+        "ALTER TABLE user ADD PRIMARY KEY (id,tenant_id);\n{{pk: Not found}}"
+    );
+}
+
+#[test]
+fn test_with_literal_binding() {
+    assert_eq!(
+        fill(
+            r#"{% with size = 10, label = "id" %}{{ label }}({{ size }}){% endwith %}"#,
+            None
+        ),
+        "id(10)"
+    );
+}
+
+#[test]
+fn test_with_data() {
+    let data = json!({ "name": "schema", "table": { "name": "user" } });
+    assert_eq!(
+        fill(
+            "begin\n    {% with table %}\n        {{ name }}\n    {% endwith %}\n{{ name }}",
+            Some(&data)
+        ),
+        "begin\n    user\nschema"
+    );
+    // The data out of 'with' is not visible
+    assert_eq!(
+        fill("{% with table %}{{ table.name }}{% endwith %}", Some(&data)),
+        "{{table.name: Not found}}"
+    );
+}
+
+#[test]
+fn test_with_indent() {
+    let data = json!({ "table": { "columns": ["id", "name"] } });
+    assert_eq!(
+        fill(
+            r#"(
+    {% with columns = table.columns %}
+        {% for column in columns %}
+        {$ join_with = ,\n $}
+        {{ column }}
+        {% endfor %}
+    {% endwith %}
+)"#,
+            Some(&data)
+        ),
+        "(\n    id,\n    name\n)"
+    );
+}
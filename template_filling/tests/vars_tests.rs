@@ -0,0 +1,113 @@
+use serde_json::json;
+use template_filling::{variables, variables_schema, variables_skeleton, FillOptions, Template};
+
+#[test]
+fn test_variables() {
+    let template = r#"{$ prefix = table_prefix $}
+{$ join_with = ,\n $}
+CREATE TABLE {{ prefix }}{{ table.name }} (
+    {% for field in custom_fields %}
+    {% if field.nullable == false %}
+    {{ field.name }} {{ field.type }} NOT NULL
+    {% endif %}
+    {% endfor %}
+);
+-- {{ $now }}"#;
+    assert_eq!(
+        variables(template, &FillOptions::default()),
+        vec![
+            "table_prefix",
+            "table.name",
+            "custom_fields",
+            "custom_fields[].nullable",
+            "custom_fields[].name",
+            "custom_fields[].type",
+        ]
+    );
+}
+
+#[test]
+fn test_variables_in_scopes() {
+    let template = r#"{% for table in tables %}
+{% for column in table.columns %}
+{% with pk = table.primary_key %}{{ pk.name }}{% endwith %}
+{{ column.name | indent(width) }}
+{% endfor %}
+{% endfor %}
+{% with schema %}{{ name }}{% endwith %}
+{% set title %}{{ project }}{% endset %}{{ title }}
+{% macro column(name, type=default_type) %}{{ name }} {{ type }} {{ charset }}{% endmacro %}
+{{ column(table_name, type=table_type) }}
+{% assert owner is defined, "{{ database }} has no owner" %}
+{% file "{{ file_name }}.sql" %}{% endfile %}"#;
+    assert_eq!(
+        Template::new(template).variables(),
+        vec![
+            "tables",
+            "tables[].columns",
+            "tables[].primary_key",
+            "tables[].primary_key.name",
+            "tables[].columns[].name",
+            "width",
+            "schema",
+            "schema.name",
+            "project",
+            "default_type",
+            "charset",
+            "table_name",
+            "table_type",
+            "owner",
+            "database",
+            "file_name",
+        ]
+    );
+}
+
+#[test]
+fn test_variables_recursive_loop() {
+    let template =
+        "{% for menu in menus recursive %}{{ menu.name }}{{ loop(menu.children) }}{% endfor %}";
+    assert_eq!(
+        variables(template, &FillOptions::default()),
+        vec!["menus", "menus[].name", "menus[].children"]
+    );
+}
+
+#[test]
+fn test_variables_skeleton() {
+    let paths: Vec<String> = ["table.name", "tables", "tables[].columns[].name", "flag"]
+        .map(str::to_owned)
+        .to_vec();
+    assert_eq!(
+        variables_skeleton(&paths),
+        json!({
+            "table": { "name": null },
+            "tables": [{ "columns": [{ "name": null }] }],
+            "flag": null
+        })
+    );
+    assert_eq!(
+        variables_schema(&paths[2..3]),
+        json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "type": "object",
+            "properties": {
+                "tables": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "columns": {
+                                "type": "array",
+                                "items": {
+                                    "type": "object",
+                                    "properties": { "name": {} }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        })
+    );
+}
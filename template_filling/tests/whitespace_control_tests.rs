@@ -0,0 +1,92 @@
+use serde_json::json;
+use template_filling::{fill, fill_with_options, FillOptions};
+
+#[test]
+fn test_strip_marker() {
+    let data = json!({ "name": "abc", "arrays": [1, 2, 3] });
+    assert_eq!(fill("a  {{- name }}  b", Some(&data)), "aabc  b");
+    assert_eq!(fill("a  {{ name -}}  b", Some(&data)), "a  abcb");
+    assert_eq!(fill("a  {{- name -}}  b", Some(&data)), "aabcb");
+    assert_eq!(fill("a\n\n  {{- name -}}\n\n  b", Some(&data)), "aabcb");
+    assert_eq!(fill("a\r\n  {{- name }}\r\nb", Some(&data)), "aabc\r\nb");
+    assert_eq!(fill("a  {#- raw -#}  b", None), "a raw b");
+    assert_eq!(fill("a\n  {$- x = 1 -$}\nb{{x}}", None), "ab1");
+    assert_eq!(
+        fill(
+            "(\n  {%- for i in arrays -%}\n  {{ i }}\n  {%- endfor -%}\n)",
+            Some(&data)
+        ),
+        "(123)"
+    );
+    assert_eq!(fill("{% if true -%}\n  Pass\n{%- endif %}", None), "Pass");
+}
+
+#[test]
+fn test_trim_blocks() {
+    let options = FillOptions {
+        trim_blocks: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        fill_with_options("{% if true %}\nPass{% endif %}\nend", None, &options),
+        "Passend"
+    );
+    assert_eq!(
+        fill_with_options("{% if true %}\r\nPass{% endif %}\r\nend", None, &options),
+        "Passend"
+    );
+    assert_eq!(
+        fill_with_options("{{ a }}\nend", Some(&json!({ "a": 1 })), &options),
+        "1\nend"
+    );
+    // The line feed of the tag alone in its line is not trimmed again
+    assert_eq!(
+        fill_with_options("a\n{% if true %}\nx\n{% endif %}\nb", None, &options),
+        "a\nx\nb"
+    );
+    assert_eq!(
+        fill_with_options("a\n  {% if false %}\nx\n  {% endif %}\nb", None, &options),
+        "a\nb"
+    );
+    assert_eq!(
+        fill_with_options(
+            "a\r\n{% if true %}\r\nx\r\n{% endif %}\r\nb",
+            None,
+            &options
+        ),
+        "a\r\nx\r\nb"
+    );
+    assert_eq!(
+        fill_with_options("a\n{% if true %}x{% endif %}\nb", None, &options),
+        "a\nxb"
+    );
+}
+
+#[test]
+fn test_lstrip_blocks() {
+    let options = FillOptions {
+        lstrip_blocks: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        fill_with_options("a\n    {% if true %}Pass{% endif %}", None, &options),
+        "a\nPass"
+    );
+    assert_eq!(
+        fill_with_options("a\n    {{ b }}", Some(&json!({ "b": 1 })), &options),
+        "a\n    1"
+    );
+    assert_eq!(
+        fill_with_options("a\n  x {% if true %}Pass{% endif %}", None, &options),
+        "a\n  x Pass"
+    );
+}
+
+#[test]
+fn test_whitespace_after_tag() {
+    let data = json!({ "arrays": [1, 2, 3] });
+    assert_eq!(
+        fill("{% for i in arrays %}{{i}}{% endfor %}  \nend", Some(&data)),
+        "123\nend"
+    );
+}
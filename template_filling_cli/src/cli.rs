@@ -1,9 +1,20 @@
-use clap::{Parser, Subcommand};
+use std::str::FromStr;
+
+use clap::{Args, Parser, Subcommand};
+use template_filling::{IndentStyle, LineEnding, Syntax, TrailingNewline};
 
 #[derive(Parser)]
 pub struct Cli {
     #[command(subcommand)]
     pub cmd: Command,
+    /// Format of the diagnostics: human, short (a line per diagnostic), json (a JSON object per line)
+    #[arg(
+        long = "message_format",
+        visible_alias = "message-format",
+        global = true,
+        default_value = "human"
+    )]
+    pub message_format: MessageFormat,
 }
 
 #[derive(Subcommand)]
@@ -22,8 +33,16 @@ pub enum Command {
         /// Filling result output file path
         #[arg(short = 'o', long = "output")]
         output_path_str: Option<String>,
+        /// Only output the content of the block with the name
+        #[arg(short = 'b', long = "block")]
+        block_name: Option<String>,
+        #[command(flatten)]
+        mode_args: FillModeArgs,
+        #[command(flatten)]
+        fill_args: FillArgs,
     },
-    /// Batch fill the template
+    /// Batch fill the template, the report and source map paths are directories
+    /// where the files are named after the templates, like 'user.tmpl.json'
     BatchFill {
         /// Template directory path
         #[arg(short = 'p', long = "template_dir")]
@@ -44,8 +63,158 @@ pub enum Command {
         /// Turn off the default loading of Json file with the same name as the template as data input
         #[arg(long = "disable_same_name_date_file")]
         disable_same_name_date_file: bool,
+        #[command(flatten)]
+        mode_args: FillModeArgs,
+        #[command(flatten)]
+        fill_args: FillArgs,
+    },
+    /// Print the AST of the template as JSON
+    Ast {
+        /// Template path
+        #[arg(short = 'p', long = "template")]
+        template_path_str: String,
+        /// AST output file path
+        #[arg(short = 'o', long = "output")]
+        output_path_str: Option<String>,
+        #[command(flatten)]
+        fill_args: FillArgs,
+    },
+    /// Print the variable paths referenced by the template
+    Vars {
+        /// Template path
+        #[arg(short = 'p', long = "template")]
+        template_path_str: String,
+        /// Output format: list, skeleton (data JSON), schema (JSON Schema)
+        #[arg(long = "format", default_value = "list")]
+        format: VarsFormat,
+        /// Output file path
+        #[arg(short = 'o', long = "output")]
+        output_path_str: Option<String>,
+        #[command(flatten)]
+        fill_args: FillArgs,
+    },
+    /// Check the templates without filling them, it exits with 1 if any problem is found
+    Check {
+        /// Template path, or the directory path of templates
+        #[arg(short = 'p', long = "template")]
+        template_path_str: String,
+        /// Data string (Json), the undefined variables are reported if the data is given
+        #[arg(short = 'd', long = "data")]
+        data_str: Option<String>,
+        /// Data file path
+        #[arg(short = 'f', long = "data_file")]
+        data_path_str: Option<String>,
+        #[command(flatten)]
+        fill_args: FillArgs,
     },
     /// Print version
     #[command(alias = "v")]
     Version,
 }
+
+/// Modes of the command fill which output more than the filled result, they can be combined
+#[derive(Args, Clone, Default)]
+pub struct FillModeArgs {
+    /// Keep going past the recoverable problems, they are reported and marked in the result
+    #[arg(long = "recover")]
+    pub recover: bool,
+    /// Report the missing placeholders, unused data keys and loop counts,
+    /// it is printed after the result, or written as JSON to the file if the path is given
+    #[arg(long = "report", num_args = 0..=1, value_name = "PATH")]
+    pub report: Option<Option<String>>,
+    /// Prefix each line of the result with the template line which produces it, like `ddl.tmpl:4[1] | id INT,`
+    #[arg(long = "annotate")]
+    pub annotate: bool,
+    /// Write the source map from the result ranges to the template lines as JSON to the file
+    #[arg(long = "source_map", value_name = "PATH")]
+    pub source_map_path_str: Option<String>,
+}
+
+/// Render-wide options of template filling
+#[derive(Args)]
+pub struct FillArgs {
+    /// Remove the first line feed after a logical tag, like `{% ... %}`,
+    /// except the tag which is alone in its line
+    #[arg(long = "trim_blocks")]
+    pub trim_blocks: bool,
+    /// Strip the whitespaces from the start of a line to a logical tag, like `{% ... %}`
+    #[arg(long = "lstrip_blocks")]
+    pub lstrip_blocks: bool,
+    /// Delimiters of the symbols, a preset name (default, bracket, angle) and/or pairs,
+    /// for example 'bracket' or 'logical=<% %> placeholder=<< >>'
+    #[arg(long = "syntax")]
+    pub syntax: Option<Syntax>,
+    /// Fill the escaped symbol head like `\{{` as text, and `\\{{` as a backslash followed by the symbol
+    #[arg(long = "escape_symbols")]
+    pub escape_symbols: bool,
+    /// Line feed style of the filled result: keep, lf, crlf, dominant
+    #[arg(long = "line_ending", default_value = "keep")]
+    pub line_ending: LineEnding,
+    /// Line feed at the end of the filled result: keep, ensure, strip
+    #[arg(long = "trailing_newline", default_value = "keep")]
+    pub trailing_newline: TrailingNewline,
+    /// Indent the continuation lines of a multi-line placeholder value
+    /// to the indent of the line where the placeholder is
+    #[arg(long = "auto_indent")]
+    pub auto_indent: bool,
+    /// Display width of a tab when measuring the indent
+    #[arg(long = "tab_width", default_value_t = 4)]
+    pub tab_width: usize,
+    /// Indent style of the filled result: keep, tabs, spaces:<width>
+    #[arg(long = "indent_style", default_value = "keep")]
+    pub indent_style: IndentStyle,
+    /// Root directory of the templates loaded by the tag 'include', default is the template directory
+    #[arg(long = "include_dir")]
+    pub include_dir: Option<String>,
+    /// Maximum depth of the recursive loop and the filter 'render'
+    #[arg(long = "recursion_limit", default_value_t = 64)]
+    pub recursion_limit: usize,
+}
+
+/// Output format of the command 'vars'
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VarsFormat {
+    /// A variable path in each line
+    List,
+    /// Skeleton data JSON
+    Skeleton,
+    /// JSON Schema of the data
+    Schema,
+}
+
+impl FromStr for VarsFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "list" => Ok(VarsFormat::List),
+            "skeleton" => Ok(VarsFormat::Skeleton),
+            "schema" => Ok(VarsFormat::Schema),
+            _ => Err(format!("Unsupported vars format: {}", s)),
+        }
+    }
+}
+
+/// Format of the diagnostics
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MessageFormat {
+    /// Location, offending line with underline and hint
+    Human,
+    /// 'path:line:column: severity: message'
+    Short,
+    /// A JSON object in each line
+    Json,
+}
+
+impl FromStr for MessageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(MessageFormat::Human),
+            "short" => Ok(MessageFormat::Short),
+            "json" => Ok(MessageFormat::Json),
+            _ => Err(format!("Unsupported message format: {}", s)),
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use std::{
+    env,
+    io::{self, IsTerminal},
+};
+
+use serde_json::json;
+use template_filling::{ast::Span, Diagnostic, FillError, FillOptions, Severity};
+
+use crate::cli::MessageFormat;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+
+/// Render the diagnostics like a compiler, coloured if the output is a terminal
+#[derive(Debug, Clone, Copy)]
+pub struct Reporter {
+    format: MessageFormat,
+    color: bool,
+}
+
+impl Reporter {
+    pub fn new(format: MessageFormat, color: bool) -> Self {
+        Self { format, color }
+    }
+
+    /// Coloured if stdout is a terminal and 'NO_COLOR' is not set
+    pub fn for_stdout(format: MessageFormat) -> Self {
+        Self::new(format, io::stdout().is_terminal() && no_color_unset())
+    }
+
+    /// Coloured if stderr is a terminal and 'NO_COLOR' is not set
+    pub fn for_stderr(format: MessageFormat) -> Self {
+        Self::new(format, io::stderr().is_terminal() && no_color_unset())
+    }
+
+    pub fn format(&self) -> MessageFormat {
+        self.format
+    }
+
+    /// @param template_content Content of the template, the offending line is not shown if None
+    pub fn render(
+        &self,
+        template_path_str: &str,
+        template_content: Option<&str>,
+        diagnostic: &Diagnostic,
+    ) -> String {
+        match self.format {
+            MessageFormat::Human => {
+                self.render_human(template_path_str, template_content, diagnostic)
+            }
+            MessageFormat::Short => format!(
+                "{}:{}:{}: {}: {}",
+                template_path_str,
+                diagnostic.line,
+                diagnostic.column,
+                diagnostic.severity,
+                diagnostic.message
+            ),
+            MessageFormat::Json => {
+                let mut value =
+                    serde_json::to_value(diagnostic).expect("Serialize diagnostic fail");
+                value["path"] = json!(template_path_str);
+                value.to_string()
+            }
+        }
+    }
+
+    /// Message without location, like the failure out of the template
+    pub fn render_message(&self, severity: Severity, message: &str) -> String {
+        match self.format {
+            MessageFormat::Human => format!(
+                "{}: {}",
+                self.paint(severity_color(severity), &severity.to_string()),
+                self.paint(BOLD, message)
+            ),
+            MessageFormat::Short => format!("{}: {}", severity, message),
+            MessageFormat::Json => json!({ "severity": severity, "message": message }).to_string(),
+        }
+    }
+
+    /// ```text
+    /// error: Tag must be balanced, endif does not close for
+    ///  --> ddl.tmpl:3:1
+    ///   |
+    /// 3 | {% endif %}
+    ///   | ^^^^^^^^^^^
+    ///   = hint: Did you mean `endfor`?
+    /// ```
+    fn render_human(
+        &self,
+        template_path_str: &str,
+        template_content: Option<&str>,
+        diagnostic: &Diagnostic,
+    ) -> String {
+        let line_number = diagnostic.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        let mut lines = vec![
+            self.render_message(diagnostic.severity, &diagnostic.message),
+            format!(
+                "{}{} {}:{}:{}",
+                gutter,
+                self.paint(BLUE, "-->"),
+                template_path_str,
+                diagnostic.line,
+                diagnostic.column
+            ),
+        ];
+        if let Some((content, line)) =
+            template_content.and_then(|content| Some((content, source_line(content, diagnostic)?)))
+        {
+            let (padding, underline) = underline(content, line, diagnostic);
+            lines.push(format!("{} {}", gutter, self.paint(BLUE, "|")));
+            lines.push(format!(
+                "{} {} {}",
+                self.paint(BLUE, &line_number),
+                self.paint(BLUE, "|"),
+                line
+            ));
+            lines.push(format!(
+                "{} {} {}{}",
+                gutter,
+                self.paint(BLUE, "|"),
+                padding,
+                self.paint(severity_color(diagnostic.severity), &underline)
+            ));
+        }
+        if let Some(hint) = &diagnostic.hint {
+            lines.push(format!(
+                "{} {} {}",
+                gutter,
+                self.paint(BLUE, "="),
+                self.paint(CYAN, &format!("hint: {}", hint))
+            ));
+        }
+        lines.join("\n")
+    }
+
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_owned()
+        }
+    }
+}
+
+fn no_color_unset() -> bool {
+    env::var_os("NO_COLOR").is_none()
+}
+
+fn severity_color(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => RED,
+        Severity::Warning => YELLOW,
+    }
+}
+
+/// Line of the diagnostic without the line feed
+fn source_line<'a>(template_content: &'a str, diagnostic: &Diagnostic) -> Option<&'a str> {
+    template_content
+        .lines()
+        .nth(diagnostic.line - 1)
+        .map(|line| line.trim_end_matches('\r'))
+}
+
+/// @return (padding before the underline, underline), the tabs in padding are kept for alignment
+fn underline(template_content: &str, line: &str, diagnostic: &Diagnostic) -> (String, String) {
+    let padding: String = line
+        .chars()
+        .take(diagnostic.column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let rest = line.chars().count().saturating_sub(diagnostic.column - 1);
+    let width = template_content
+        .get(diagnostic.span.start..diagnostic.span.end)
+        .map_or(0, |text| text.chars().count());
+    // The span in multiple lines is underlined to the end of the line
+    let width = width.min(rest).max(1);
+    (padding, "^".repeat(width))
+}
+
+/// Diagnostic of the failed filling, the span is the tag at the location.
+/// @param template_content Content of the template where the error is
+pub fn fill_error_diagnostic(
+    template_content: Option<&str>,
+    error: &FillError,
+    options: &FillOptions,
+) -> Diagnostic {
+    let span = template_content
+        .map(|content| {
+            let start = content
+                .split_inclusive('\n')
+                .take(error.line - 1)
+                .map(str::len)
+                .sum::<usize>();
+            let start = start
+                + content[start..]
+                    .chars()
+                    .take(error.column - 1)
+                    .map(char::len_utf8)
+                    .sum::<usize>();
+            let tail = &options.syntax.logical.1;
+            let end = content[start..]
+                .find(tail.as_str())
+                .map_or(start + 1, |pos| start + pos + tail.len());
+            Span::new(start, end)
+        })
+        .unwrap_or(Span::new(0, 0));
+    Diagnostic {
+        severity: Severity::Error,
+        message: error.message.to_owned(),
+        span,
+        line: error.line,
+        column: error.column,
+        hint: None,
+        template: error.template.clone(),
+    }
+}
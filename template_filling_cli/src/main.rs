@@ -1,30 +1,52 @@
 use std::{
-    fs,
-    path::Path,
+    fs, panic,
+    path::{Component, Path, PathBuf},
+    process,
+    rc::Rc,
     time::{Instant, SystemTime, UNIX_EPOCH},
 };
 
 use clap::Parser;
-use cli::{Cli, Command};
+use cli::{Cli, Command, FillArgs, FillModeArgs, MessageFormat, VarsFormat};
+use diagnostic::Reporter;
 use serde_json::Value;
+use template_filling::{
+    Diagnostic, FileSystemLoader, FillError, FillOptions, RenderMode, RenderOutput, Severity,
+    TemplateLoader,
+};
 
 #[cfg(test)]
 mod tests;
 
 mod cli;
+mod diagnostic;
 
 const TEMPLATE_SUFFIX: &str = ".template";
 const TEMPLATE_SUFFIX_SHORT: &str = ".tmpl";
 
 fn main() {
     let cli = Cli::parse();
+    let reporter = Reporter::for_stderr(cli.message_format);
+    set_panic_hook(reporter);
     match cli.cmd {
         Command::Fill {
             template_path_str,
             data_str,
             data_path_str,
             output_path_str,
-        } => fill(template_path_str, data_str, data_path_str, output_path_str),
+            block_name,
+            mode_args,
+            fill_args,
+        } => fill(
+            template_path_str,
+            data_str,
+            data_path_str,
+            output_path_str,
+            block_name,
+            mode_args,
+            &fill_args.into(),
+            reporter,
+        ),
         Command::BatchFill {
             template_directory_path_str,
             template_tag,
@@ -32,6 +54,8 @@ fn main() {
             data_path_str,
             output_directory_path_str,
             disable_same_name_date_file,
+            mode_args,
+            fill_args,
         } => batch_fill(
             template_directory_path_str,
             template_tag,
@@ -39,11 +63,63 @@ fn main() {
             data_path_str,
             output_directory_path_str,
             disable_same_name_date_file,
+            mode_args,
+            &fill_args.into(),
+            reporter,
+        ),
+        Command::Ast {
+            template_path_str,
+            output_path_str,
+            fill_args,
+        } => ast(template_path_str, output_path_str, &fill_args.into()),
+        Command::Vars {
+            template_path_str,
+            format,
+            output_path_str,
+            fill_args,
+        } => vars(
+            template_path_str,
+            format,
+            output_path_str,
+            &fill_args.into(),
+        ),
+        Command::Check {
+            template_path_str,
+            data_str,
+            data_path_str,
+            fill_args,
+        } => check(
+            template_path_str,
+            data_str,
+            data_path_str,
+            &fill_args.into(),
+            Reporter::for_stdout(cli.message_format),
         ),
         Command::Version => version(),
     }
 }
 
+impl From<FillArgs> for FillOptions {
+    fn from(args: FillArgs) -> Self {
+        FillOptions {
+            trim_blocks: args.trim_blocks,
+            lstrip_blocks: args.lstrip_blocks,
+            syntax: args.syntax.unwrap_or_default(),
+            escape_symbols: args.escape_symbols,
+            line_ending: args.line_ending,
+            trailing_newline: args.trailing_newline,
+            auto_indent: args.auto_indent,
+            tab_width: args.tab_width,
+            indent_style: args.indent_style,
+            loader: args.include_dir.map(|include_dir| {
+                Rc::new(FileSystemLoader::new(include_dir)) as Rc<dyn TemplateLoader>
+            }),
+            recursion_limit: args.recursion_limit,
+            custom_tags: Vec::new(),
+        }
+    }
+}
+
 struct Template {
     pub path_str: String,
     pub output_path_str: Option<String>,
@@ -68,64 +144,295 @@ impl Template {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn fill(
     template_path_str: String,
     data_str: Option<String>,
     data_path_str: Option<String>,
     output_path_str: Option<String>,
+    block_name: Option<String>,
+    mode_args: FillModeArgs,
+    options: &FillOptions,
+    reporter: Reporter,
 ) {
     let template_path = Path::new(&template_path_str);
-    let template = package_template(&template_path, &None, &output_path_str);
+    let template = package_template(template_path, &None, &output_path_str);
     let data = load_data(&data_str, &data_path_str);
-    fill_0(&template, &data);
+    let template_directory_path = template_path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let options = with_default_loader(options, template_directory_path);
+    fill_0(&template, &data, block_name, &mode_args, &options, reporter);
 }
 
-fn fill_0(template: &Template, data: &Option<Value>) {
-    // Filling
+/// Print the panic message as a diagnostic, instead of the default message with the source location
+fn set_panic_hook(reporter: Reporter) {
+    panic::set_hook(Box::new(move |info| {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| String::from("Unknown error"));
+        eprintln!("{}", reporter.render_message(Severity::Error, &message));
+    }));
+}
+
+/// The templates are included from the template directory if the include directory is not given
+fn with_default_loader(options: &FillOptions, template_directory_path: &Path) -> FillOptions {
+    let mut options = options.clone();
+    if options.loader.is_none() {
+        options.loader = Some(Rc::new(FileSystemLoader::new(template_directory_path)));
+    }
+    options
+}
+
+/// Fill with what is recorded by the mode arguments, the recoverable problems are reported before the result,
+/// and the report after it. The process exits with 1 after the output if any recovered problem is an error
+fn fill_0(
+    template: &Template,
+    data: &Option<Value>,
+    block_name: Option<String>,
+    mode_args: &FillModeArgs,
+    options: &FillOptions,
+    reporter: Reporter,
+) {
     let template_content = template.get_content();
-    let filled = if cfg!(debug_assertions) && cfg!(not(test)) {
+    // The problems are reported with the result in the recovery mode
+    if !mode_args.recover {
+        check_before_fill(template, &template_content, options, reporter);
+    }
+    let mode = RenderMode {
+        block: block_name,
+        recover: mode_args.recover,
+        report: mode_args.report.is_some(),
+        source_map: mode_args.annotate || mode_args.source_map_path_str.is_some(),
+    };
+    // Filling
+    let output = if cfg!(debug_assertions) && cfg!(not(test)) {
         let start = Instant::now();
-        let filled = template_filling::fill(template_content, data.as_ref());
+        let output = fill_content(template_content.clone(), data.as_ref(), &mode, options);
         let elapsed = start.elapsed();
         println!("[debug] fill::fill_template time elapsed is {:?}", elapsed);
         if elapsed.as_millis() >= 5 {
             panic!("The execution time of template_filling::fill has exceeded the 5ms performance threshold")
         }
-        filled
+        output
     } else {
-        template_filling::fill(template_content, data.as_ref())
-    };
-    // Output or print result
-    if let Some(output_path_str) = template.output_path_str.as_ref() {
-        println!("Output filled result to {}", output_path_str);
-        // Create output path parent
-        let output_path = Path::new(&output_path_str);
-        if let Some(parent_path) = output_path.parent() {
-            if !fs::exists(parent_path).expect("Check parent of output path exists fail") {
-                fs::create_dir_all(parent_path).expect(
-                    "Create all of output path parent components if they are missing, but fail",
-                );
+        fill_content(template_content.clone(), data.as_ref(), &mode, options)
+    }
+    .unwrap_or_else(|e| {
+        // Failed by the tags 'assert' and 'error'
+        eprintln!(
+            "{}",
+            render_fill_error(&template.path_str, &template_content, &e, options, reporter)
+        );
+        process::exit(1)
+    });
+    for diagnostic in &output.diagnostics {
+        eprintln!(
+            "{}",
+            render_diagnostic(
+                &template.path_str,
+                &template_content,
+                diagnostic,
+                options,
+                reporter
+            )
+        );
+        // Blank line between the multi-line diagnostics
+        if reporter.format() == MessageFormat::Human {
+            eprintln!();
+        }
+    }
+    // Write the source map, and prefix each line of the result with the template line which produces it
+    let mut filled = output.output;
+    if let Some(source_map) = output.source_map {
+        if let Some(source_map_path_str) = &mode_args.source_map_path_str {
+            println!("Output source map to {}", source_map_path_str);
+            let source_map_json =
+                serde_json::to_string_pretty(&source_map).expect("Serialize source map fail");
+            write_output(Path::new(source_map_path_str), source_map_json);
+        }
+        if mode_args.annotate {
+            filled = source_map.annotate(&filled, &template.path_str);
+        }
+    }
+    output_filled(template, filled, output.files);
+    // Print the report, or write it as JSON to the file if the path is given
+    if let Some(report) = output.report {
+        match mode_args.report.as_ref().and_then(Option::as_ref) {
+            Some(report_path_str) => {
+                println!("Output render report to {}", report_path_str);
+                let report_json =
+                    serde_json::to_string_pretty(&report).expect("Serialize render report fail");
+                write_output(Path::new(report_path_str), report_json);
             }
+            None => println!("Render report:\n{}", report),
         }
-        // Write output
-        fs::write(output_path, filled).expect("Output filled result fail");
-    } else {
-        println!("Filled result:\n{}", filled);
+    }
+    if output
+        .diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity == Severity::Error)
+    {
+        process::exit(1)
     }
 }
 
+/// Check the template before filling, the problems which fail the filling are reported with locations
+fn check_before_fill(
+    template: &Template,
+    template_content: &str,
+    options: &FillOptions,
+    reporter: Reporter,
+) {
+    let errors: Vec<_> = template_filling::check(template_content, None, options)
+        .into_iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .collect();
+    if !errors.is_empty() {
+        for error in &errors {
+            eprintln!(
+                "{}",
+                reporter.render(&template.path_str, Some(template_content), error)
+            );
+        }
+        process::exit(1)
+    }
+}
+
+fn output_filled(template: &Template, filled: String, files: Vec<(String, String)>) {
+    // Output or print result, it is skipped if the template only emits files
+    if files.is_empty() || !filled.trim().is_empty() {
+        if let Some(output_path_str) = template.output_path_str.as_ref() {
+            println!("Output filled result to {}", output_path_str);
+            write_output(Path::new(&output_path_str), filled);
+        } else {
+            println!("Filled result:\n{}", filled);
+        }
+    }
+    // Output or print files emitted by the tag 'file', they are relative to the output directory
+    let output_directory_path = template
+        .output_path_str
+        .as_ref()
+        .map(|output_path_str| Path::new(output_path_str).parent().unwrap_or(Path::new("")));
+    for (file_path_str, content) in files {
+        if let Some(output_directory_path) = output_directory_path {
+            let file_path = get_output_file_path(output_directory_path, &file_path_str);
+            println!("Output filled file to {}", file_path.display());
+            write_output(&file_path, content);
+        } else {
+            println!("Filled file {}:\n{}", file_path_str, content);
+        }
+    }
+}
+
+/// The error may be in the included template, which is loaded again to show the offending line
+fn render_fill_error(
+    template_path_str: &str,
+    template_content: &str,
+    error: &FillError,
+    options: &FillOptions,
+    reporter: Reporter,
+) -> String {
+    let content = match &error.template {
+        Some(name) => load_included(name, options),
+        None => Some(template_content.to_owned()),
+    };
+    let diagnostic = diagnostic::fill_error_diagnostic(content.as_deref(), error, options);
+    render_diagnostic(
+        template_path_str,
+        template_content,
+        &diagnostic,
+        options,
+        reporter,
+    )
+}
+
+/// The diagnostic may be in the included template, which is loaded again to show the offending line
+fn render_diagnostic(
+    template_path_str: &str,
+    template_content: &str,
+    diagnostic: &Diagnostic,
+    options: &FillOptions,
+    reporter: Reporter,
+) -> String {
+    let (path_str, content) = match &diagnostic.template {
+        Some(name) => (name.as_str(), load_included(name, options)),
+        None => (template_path_str, Some(template_content.to_owned())),
+    };
+    reporter.render(path_str, content.as_deref(), diagnostic)
+}
+
+fn load_included(name: &str, options: &FillOptions) -> Option<String> {
+    options
+        .loader
+        .as_ref()
+        .and_then(|loader| loader.load(name).ok())
+}
+
+fn write_output(output_path: &Path, content: String) {
+    // Create output path parent
+    if let Some(parent_path) = output_path.parent() {
+        if !fs::exists(parent_path).expect("Check parent of output path exists fail") {
+            fs::create_dir_all(parent_path).expect(
+                "Create all of output path parent components if they are missing, but fail",
+            );
+        }
+    }
+    // Write output
+    fs::write(output_path, content).expect("Output filled result fail");
+}
+
+/// The file path must be relative and can not escape the output directory
+fn get_output_file_path(output_directory_path: &Path, file_path_str: &str) -> PathBuf {
+    let file_path = Path::new(file_path_str);
+    if file_path_str.is_empty()
+        || file_path
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        panic!(
+            "File path must be relative to the output directory: {}",
+            file_path_str
+        )
+    }
+    output_directory_path.join(file_path)
+}
+
+/// Fill the whole template, or only the block if the block name is given, the files are dropped for the block
+fn fill_content(
+    template_content: String,
+    data: Option<&Value>,
+    mode: &RenderMode,
+    options: &FillOptions,
+) -> Result<RenderOutput, FillError> {
+    let mut output = template_filling::try_fill_with_mode(template_content, data, options, mode)?;
+    if let Some(block_name) = &mode.block {
+        output.output = output
+            .block
+            .take()
+            .unwrap_or_else(|| panic!("Block not found: {}", block_name));
+        output.files.clear();
+    }
+    Ok(output)
+}
+
 fn load_data(data_str: &Option<String>, data_path_str: &Option<String>) -> Option<Value> {
     if let Some(data_str) = data_str {
-        serde_json::from_str(&data_str).expect("Parse data content fail")
+        serde_json::from_str(data_str).expect("Parse data content fail")
     } else if let Some(data_path_str) = data_path_str {
         let data_path = Path::new(&data_path_str);
-        let data_content = fs::read_to_string(&data_path).expect("Read data fail");
+        let data_content = fs::read_to_string(data_path).expect("Read data fail");
         serde_json::from_str(&data_content).expect("Parse data content fail")
     } else {
         None
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn batch_fill(
     template_directory_path_str: String,
     template_tag: Option<String>,
@@ -133,6 +440,9 @@ fn batch_fill(
     data_path_str: Option<String>,
     output_directory_path_str: Option<String>,
     disable_same_name_date_file: bool,
+    mode_args: FillModeArgs,
+    options: &FillOptions,
+    reporter: Reporter,
 ) {
     // Find available templates
     let template_directory_path = Path::new(&template_directory_path_str);
@@ -150,6 +460,7 @@ fn batch_fill(
     }
     // Load data
     let mut data = load_data(&data_str, &data_path_str);
+    let options = with_default_loader(options, template_directory_path);
     // Loop available templates
     for template in templates.unwrap() {
         println!(
@@ -161,10 +472,42 @@ fn batch_fill(
         if data.is_none() && !disable_same_name_date_file {
             data = template.get_same_name_data_file_value();
         }
-        fill_0(&template, &data);
+        // The report and source map of each template are written into the directories
+        let template_mode_args = FillModeArgs {
+            report: mode_args.report.clone().map(|report_directory_path_str| {
+                report_directory_path_str
+                    .map(|path_str| get_template_record_path(&path_str, &template))
+            }),
+            source_map_path_str: mode_args
+                .source_map_path_str
+                .as_ref()
+                .map(|path_str| get_template_record_path(path_str, &template)),
+            ..mode_args.clone()
+        };
+        fill_0(
+            &template,
+            &data,
+            None,
+            &template_mode_args,
+            &options,
+            reporter,
+        );
     }
 }
 
+/// Path of the JSON file in the directory which is named after the template, like 'user.tmpl.json'
+fn get_template_record_path(directory_path_str: &str, template: &Template) -> String {
+    let template_file_name = Path::new(&template.path_str)
+        .file_name()
+        .unwrap()
+        .to_str()
+        .unwrap();
+    Path::new(directory_path_str)
+        .join(format!("{}.json", template_file_name))
+        .display()
+        .to_string()
+}
+
 fn find_all_available_templates(
     template_directory_path: &Path,
     template_tag: &Option<String>,
@@ -258,16 +601,14 @@ fn package_template(
 
     Template {
         path_str: template_path.to_str().unwrap().to_owned(),
-        output_path_str: if let Some(output_directory) = output_directory_path_str {
-            Some(format!(
+        output_path_str: output_directory_path_str.as_ref().map(|output_directory| {
+            format!(
                 "{}{}{}",
                 output_directory,
                 std::path::MAIN_SEPARATOR,
                 output_file_name
-            ))
-        } else {
-            None
-        },
+            )
+        }),
         version,
     }
 }
@@ -289,6 +630,106 @@ fn find_unicode_pos(text: &str, pattern: &str) -> Option<usize> {
         .map(|(byte_pos, _)| text[..byte_pos].chars().count())
 }
 
+fn ast(template_path_str: String, output_path_str: Option<String>, options: &FillOptions) {
+    let template_content = fs::read_to_string(template_path_str).expect("Read template fail");
+    let ast = ast_json(&template_content, options);
+    if let Some(output_path_str) = output_path_str {
+        println!("Output AST to {}", output_path_str);
+        write_output(Path::new(&output_path_str), ast);
+    } else {
+        println!("{}", ast);
+    }
+}
+
+fn ast_json(template_content: &str, options: &FillOptions) -> String {
+    serde_json::to_string_pretty(&template_filling::parse(template_content, options))
+        .expect("Serialize AST fail")
+}
+
+fn vars(
+    template_path_str: String,
+    format: VarsFormat,
+    output_path_str: Option<String>,
+    options: &FillOptions,
+) {
+    let template_content = fs::read_to_string(template_path_str).expect("Read template fail");
+    let vars = vars_output(&template_content, format, options);
+    if let Some(output_path_str) = output_path_str {
+        println!("Output variables to {}", output_path_str);
+        write_output(Path::new(&output_path_str), vars);
+    } else {
+        println!("{}", vars);
+    }
+}
+
+fn vars_output(template_content: &str, format: VarsFormat, options: &FillOptions) -> String {
+    let paths = template_filling::variables(template_content, options);
+    let json = match format {
+        VarsFormat::List => return paths.join("\n"),
+        VarsFormat::Skeleton => template_filling::variables_skeleton(&paths),
+        VarsFormat::Schema => template_filling::variables_schema(&paths),
+    };
+    serde_json::to_string_pretty(&json).expect("Serialize variables fail")
+}
+
+fn check(
+    template_path_str: String,
+    data_str: Option<String>,
+    data_path_str: Option<String>,
+    options: &FillOptions,
+    reporter: Reporter,
+) {
+    let template_path = Path::new(&template_path_str);
+    let templates = if template_path.is_dir() {
+        find_all_available_templates(template_path, &None, &None).unwrap_or_default()
+    } else {
+        vec![package_template(template_path, &None, &None)]
+    };
+    let data = load_data(&data_str, &data_path_str);
+    let mut problem_count = 0;
+    for template in &templates {
+        let problems = check_output(
+            &template.path_str,
+            &template.get_content(),
+            data.as_ref(),
+            options,
+            reporter,
+        );
+        for problem in &problems {
+            println!("{}", problem);
+            // Blank line between the multi-line diagnostics
+            if reporter.format() == MessageFormat::Human {
+                println!();
+            }
+        }
+        problem_count += problems.len();
+    }
+    if reporter.format() != MessageFormat::Json {
+        println!(
+            "Checked {} templates, {} problems",
+            templates.len(),
+            problem_count
+        );
+    }
+    if problem_count > 0 {
+        process::exit(1)
+    }
+}
+
+/// @return Problems rendered by the reporter
+fn check_output(
+    template_path_str: &str,
+    template_content: &str,
+    data: Option<&Value>,
+    options: &FillOptions,
+    reporter: Reporter,
+) -> Vec<String> {
+    template_filling::check(template_content, data, options)
+        .iter()
+        .map(|diagnostic| reporter.render(template_path_str, Some(template_content), diagnostic))
+        .collect()
+}
+
 fn version() {
     println!("{}", env!("CARGO_PKG_VERSION"));
 }
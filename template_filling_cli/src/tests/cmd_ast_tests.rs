@@ -0,0 +1,30 @@
+use serde_json::{json, Value};
+use template_filling::FillOptions;
+
+use crate::ast_json;
+
+#[test]
+fn test_ast_json() {
+    let ast: Value = serde_json::from_str(&ast_json(
+        "{% if a %}\n  {{ a }}\n{% endif %}",
+        &FillOptions::default(),
+    ))
+    .unwrap();
+    let tag = &ast["lines"][0]["nodes"][0];
+    assert_eq!(tag["type"], "tag");
+    assert_eq!(tag["span"], json!({ "start": 0, "end": 10 }));
+    assert_eq!(tag["end_span"], json!({ "start": 21, "end": 32 }));
+    assert_eq!(tag["tag"]["kind"], "if");
+    assert_eq!(tag["tag"]["condition"]["left"]["text"], "a");
+    let placeholder = &tag["body"]["lines"][0];
+    assert_eq!(placeholder["indent_width"], 2);
+    assert_eq!(
+        placeholder["nodes"][0],
+        json!({
+            "span": { "start": 13, "end": 20 },
+            "first_in_line": true,
+            "type": "placeholder",
+            "expression": "a"
+        })
+    );
+}
@@ -0,0 +1,36 @@
+use serde_json::json;
+use template_filling::FillOptions;
+
+use crate::{check_output, cli::MessageFormat, diagnostic::Reporter};
+
+#[test]
+fn test_check_output() {
+    assert_eq!(
+        check_output(
+            "ddl.tmpl",
+            "{% for column in columns %}\n{{ column.name }}\n{% endif %}",
+            None,
+            &FillOptions::default(),
+            Reporter::new(MessageFormat::Short, false)
+        ),
+        vec![
+            "ddl.tmpl:1:1: error: Unclosed tag for, missing endfor",
+            "ddl.tmpl:3:1: error: Tag must be balanced, endif does not close for",
+        ]
+    );
+}
+
+#[test]
+fn test_check_output_with_data() {
+    let data = json!({ "table": "user" });
+    assert_eq!(
+        check_output(
+            "ddl.tmpl",
+            "CREATE TABLE {{ table }} ({{ columns }});",
+            Some(&data),
+            &FillOptions::default(),
+            Reporter::new(MessageFormat::Short, false)
+        ),
+        vec!["ddl.tmpl:1:27: warning: Undefined variable: columns"]
+    );
+}
@@ -0,0 +1,121 @@
+use std::rc::Rc;
+
+use serde_json::{json, Value};
+use template_filling::{FillError, FillOptions, MemoryLoader, Severity};
+
+use crate::{
+    check_output,
+    cli::MessageFormat,
+    diagnostic::{self, Reporter},
+    render_diagnostic,
+};
+
+const TEMPLATE: &str =
+    "CREATE TABLE user (\n\t{% for column in columns %}\n\t{{ column }}\n\t{% endfi %}\n);";
+
+#[test]
+fn test_human_diagnostic() {
+    let problems = check_output(
+        "ddl.tmpl",
+        TEMPLATE,
+        None,
+        &FillOptions::default(),
+        Reporter::new(MessageFormat::Human, false),
+    );
+    assert_eq!(
+        problems[1],
+        "error: Unsupported tag: endfi
+ --> ddl.tmpl:4:2
+  |
+4 | \t{% endfi %}
+  | \t^^^^^^^^^^^
+  = hint: Did you mean `endif`?"
+    );
+}
+
+#[test]
+fn test_colored_diagnostic() {
+    let problems = check_output(
+        "ddl.tmpl",
+        "{{ name | upper }}",
+        None,
+        &FillOptions::default(),
+        Reporter::new(MessageFormat::Human, true),
+    );
+    assert!(problems[0].starts_with("\x1b[1;31merror\x1b[0m: \x1b[1mUnsupported filter: upper"));
+    assert!(problems[0].contains("\x1b[1;31m^^^^^^^^^^^^^^^^^^\x1b[0m"));
+}
+
+#[test]
+fn test_json_diagnostic() {
+    let problems = check_output(
+        "ddl.tmpl",
+        TEMPLATE,
+        None,
+        &FillOptions::default(),
+        Reporter::new(MessageFormat::Json, false),
+    );
+    let problem: Value = serde_json::from_str(&problems[0]).unwrap();
+    assert_eq!(
+        problem,
+        json!({
+            "path": "ddl.tmpl",
+            "severity": "error",
+            "message": "Unclosed tag for, missing endfor",
+            "span": { "start": 21, "end": 48 },
+            "line": 2,
+            "column": 2,
+            "hint": "Add `endfor` after its body"
+        })
+    );
+}
+
+#[test]
+fn test_fill_error_diagnostic() {
+    let template = "Hello,\n  {% error \"name is required\" %}";
+    let error = FillError {
+        message: String::from("name is required"),
+        template: None,
+        line: 2,
+        column: 3,
+    };
+    let diagnostic =
+        diagnostic::fill_error_diagnostic(Some(template), &error, &FillOptions::default());
+    assert_eq!(diagnostic.severity, Severity::Error);
+    assert_eq!(
+        Reporter::new(MessageFormat::Human, false).render(
+            "hello.tmpl",
+            Some(template),
+            &diagnostic
+        ),
+        "error: name is required
+ --> hello.tmpl:2:3
+  |
+2 |   {% error \"name is required\" %}
+  |   ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^"
+    );
+}
+
+#[test]
+fn test_recovered_diagnostic_in_include() {
+    let mut loader = MemoryLoader::new();
+    loader.insert("column.tmpl", "{{ name }} {{ type }}");
+    let options = FillOptions {
+        loader: Some(Rc::new(loader)),
+        ..FillOptions::default()
+    };
+    let template = "({% include \"column.tmpl\" %})";
+    let (filled, diagnostics) =
+        template_filling::fill_with_diagnostics(template, Some(&json!({ "name": "id" })), &options);
+    assert_eq!(filled, "(id {{type: Not found}})");
+    assert_eq!(
+        render_diagnostic(
+            "ddl.tmpl",
+            template,
+            &diagnostics[0],
+            &options,
+            Reporter::new(MessageFormat::Short, false)
+        ),
+        "column.tmpl:1:12: warning: Variable not found: type"
+    );
+}
@@ -0,0 +1,89 @@
+use std::{env, fs};
+
+use serde_json::{json, Value};
+use template_filling::FillOptions;
+
+use crate::{
+    cli::{FillModeArgs, MessageFormat},
+    diagnostic::Reporter,
+    fill,
+};
+
+#[test]
+fn test_fill_annotate_and_source_map() {
+    let output_directory_path = env::temp_dir().join("template_filling_cmd_fill_annotate");
+    let source_map_path = env::temp_dir().join("template_filling_cmd_fill_source_map.json");
+    fill(
+        "src/tests/cmd_fill_tests_template.tmpl".to_owned(),
+        Some("{\"test_fill_data_attr\":\"abc\"}".to_owned()),
+        None,
+        Some(output_directory_path.display().to_string()),
+        None,
+        FillModeArgs {
+            annotate: true,
+            source_map_path_str: Some(source_map_path.display().to_string()),
+            ..FillModeArgs::default()
+        },
+        &FillOptions::default(),
+        Reporter::new(MessageFormat::Human, false),
+    );
+    let output_path = fs::read_dir(&output_directory_path)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    let annotated = fs::read_to_string(output_path).unwrap();
+    let source_map: Value =
+        serde_json::from_str(&fs::read_to_string(&source_map_path).unwrap()).unwrap();
+    fs::remove_dir_all(&output_directory_path).unwrap();
+    fs::remove_file(&source_map_path).unwrap();
+    assert_eq!(
+        annotated,
+        "src/tests/cmd_fill_tests_template.tmpl:1 | This is template filling demo. This is placeholder filling: abc"
+    );
+    assert_eq!(
+        source_map,
+        json!({
+            "mappings": [
+                { "output": { "start": 0, "end": 60 }, "line": 1, "column": 1, "iterations": [] },
+                { "output": { "start": 60, "end": 63 }, "line": 1, "column": 61, "iterations": [] }
+            ]
+        })
+    );
+}
+
+#[test]
+fn test_fill_annotate_with_files() {
+    let template_directory_path = env::temp_dir().join("template_filling_cmd_fill_annotate_files");
+    let output_directory_path = template_directory_path.join("outputs");
+    let template_path = template_directory_path.join("user.tmpl");
+    fs::create_dir_all(&template_directory_path).unwrap();
+    fs::write(
+        &template_path,
+        "{% file \"sql/{{ name }}.sql\" %}CREATE TABLE {{ name }};{% endfile %}",
+    )
+    .unwrap();
+    fill(
+        template_path.display().to_string(),
+        Some("{\"name\":\"user\"}".to_owned()),
+        None,
+        Some(output_directory_path.display().to_string()),
+        None,
+        FillModeArgs {
+            annotate: true,
+            source_map_path_str: Some(
+                template_directory_path
+                    .join("source_map.json")
+                    .display()
+                    .to_string(),
+            ),
+            ..FillModeArgs::default()
+        },
+        &FillOptions::default(),
+        Reporter::new(MessageFormat::Human, false),
+    );
+    let file_content = fs::read_to_string(output_directory_path.join("sql/user.sql")).unwrap();
+    fs::remove_dir_all(&template_directory_path).unwrap();
+    assert_eq!(file_content, "CREATE TABLE user;");
+}
@@ -0,0 +1,31 @@
+use serde_json::json;
+use template_filling::{FillOptions, RenderMode};
+
+use crate::fill_content;
+
+#[test]
+fn test_fill_error() {
+    let template = "{% assert name is defined, \"name is required\" %}\nHello, {{ name }}!";
+    assert_eq!(
+        fill_content(
+            template.to_owned(),
+            Some(&json!({ "name": "World" })),
+            &RenderMode::default(),
+            &FillOptions::default()
+        )
+        .unwrap()
+        .output,
+        "Hello, World!"
+    );
+    assert_eq!(
+        fill_content(
+            template.to_owned(),
+            None,
+            &RenderMode::default(),
+            &FillOptions::default()
+        )
+        .unwrap_err()
+        .to_string(),
+        "name is required, at 1:1"
+    );
+}
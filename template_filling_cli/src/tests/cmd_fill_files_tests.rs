@@ -0,0 +1,28 @@
+use std::path::Path;
+
+use crate::get_output_file_path;
+
+#[test]
+fn test_output_file_path() {
+    let output_directory_path = Path::new("outputs");
+    assert_eq!(
+        get_output_file_path(output_directory_path, "sql/user.sql"),
+        Path::new("outputs/sql/user.sql")
+    );
+    assert_eq!(
+        get_output_file_path(output_directory_path, "./user.rs"),
+        Path::new("outputs/./user.rs")
+    );
+}
+
+#[test]
+#[should_panic(expected = "File path must be relative to the output directory")]
+fn test_output_file_path_escape() {
+    get_output_file_path(Path::new("outputs"), "../user.sql");
+}
+
+#[test]
+#[should_panic(expected = "File path must be relative to the output directory")]
+fn test_output_file_path_absolute() {
+    get_output_file_path(Path::new("outputs"), "/etc/user.sql");
+}
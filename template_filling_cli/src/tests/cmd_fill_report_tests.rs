@@ -0,0 +1,70 @@
+use std::{env, fs};
+
+use serde_json::{json, Value};
+use template_filling::FillOptions;
+
+use crate::{
+    batch_fill,
+    cli::{FillModeArgs, MessageFormat},
+    diagnostic::Reporter,
+    fill,
+};
+
+#[test]
+fn test_fill_report_json() {
+    let report_path = env::temp_dir().join("template_filling_cmd_fill_report.json");
+    fill(
+        "src/tests/cmd_fill_tests_template.tmpl".to_owned(),
+        Some("{\"test_fill_data_attr\":\"abc\",\"unused\":1}".to_owned()),
+        None,
+        None,
+        None,
+        FillModeArgs {
+            report: Some(Some(report_path.display().to_string())),
+            ..FillModeArgs::default()
+        },
+        &FillOptions::default(),
+        Reporter::new(MessageFormat::Human, false),
+    );
+    let report: Value = serde_json::from_str(&fs::read_to_string(&report_path).unwrap()).unwrap();
+    fs::remove_file(&report_path).unwrap();
+    assert_eq!(
+        report,
+        json!({
+            "missing_placeholders": [],
+            "unused_keys": ["unused"],
+            "loops": []
+        })
+    );
+}
+
+#[test]
+fn test_batch_fill_report_json() {
+    let template_directory_path = env::temp_dir().join("template_filling_cmd_batch_fill_report");
+    let report_directory_path = template_directory_path.join("reports");
+    fs::create_dir_all(&template_directory_path).unwrap();
+    fs::write(template_directory_path.join("a.tmpl"), "{{ a }}").unwrap();
+    fs::write(template_directory_path.join("b.tmpl"), "{{ b }}").unwrap();
+    batch_fill(
+        template_directory_path.display().to_string(),
+        None,
+        Some("{\"a\":1,\"b\":2}".to_owned()),
+        None,
+        None,
+        false,
+        FillModeArgs {
+            report: Some(Some(report_directory_path.display().to_string())),
+            ..FillModeArgs::default()
+        },
+        &FillOptions::default(),
+        Reporter::new(MessageFormat::Human, false),
+    );
+    let read_report = |name: &str| -> Value {
+        serde_json::from_str(&fs::read_to_string(report_directory_path.join(name)).unwrap())
+            .unwrap()
+    };
+    let (report_a, report_b) = (read_report("a.tmpl.json"), read_report("b.tmpl.json"));
+    fs::remove_dir_all(&template_directory_path).unwrap();
+    assert_eq!(report_a["unused_keys"], json!(["b"]));
+    assert_eq!(report_b["unused_keys"], json!(["a"]));
+}
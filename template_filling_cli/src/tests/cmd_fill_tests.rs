@@ -1,4 +1,10 @@
-use crate::fill;
+use template_filling::FillOptions;
+
+use crate::{
+    cli::{FillModeArgs, MessageFormat},
+    diagnostic::Reporter,
+    fill,
+};
 
 #[test]
 fn test() {
@@ -12,6 +18,10 @@ fn test() {
         Some("{\"test_fill_data_attr\":\"abc\"}".to_owned()),
         None,
         None,
+        None,
+        FillModeArgs::default(),
+        &FillOptions::default(),
+        Reporter::new(MessageFormat::Human, false),
     );
 
     // // 恢复原有 stdout（避免影响其他测试）
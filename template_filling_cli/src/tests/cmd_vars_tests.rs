@@ -0,0 +1,43 @@
+use serde_json::{json, Value};
+use template_filling::FillOptions;
+
+use crate::{cli::VarsFormat, vars_output};
+
+const TEMPLATE: &str = "{% for field in custom_fields %}{{ field.name }}{% endfor %}{{ table }}";
+
+#[test]
+fn test_vars_list() {
+    assert_eq!(
+        vars_output(TEMPLATE, VarsFormat::List, &FillOptions::default()),
+        "custom_fields\ncustom_fields[].name\ntable"
+    );
+}
+
+#[test]
+fn test_vars_skeleton() {
+    let skeleton: Value = serde_json::from_str(&vars_output(
+        TEMPLATE,
+        VarsFormat::Skeleton,
+        &FillOptions::default(),
+    ))
+    .unwrap();
+    assert_eq!(
+        skeleton,
+        json!({ "custom_fields": [{ "name": null }], "table": null })
+    );
+}
+
+#[test]
+fn test_vars_schema() {
+    let schema: Value = serde_json::from_str(&vars_output(
+        TEMPLATE,
+        VarsFormat::Schema,
+        &FillOptions::default(),
+    ))
+    .unwrap();
+    assert_eq!(schema["type"], "object");
+    assert_eq!(
+        schema["properties"]["custom_fields"]["items"]["properties"]["name"],
+        json!({})
+    );
+}
@@ -1 +1,9 @@
+mod cmd_ast_tests;
+mod cmd_check_tests;
+mod cmd_diagnostic_tests;
+mod cmd_fill_annotate_tests;
+mod cmd_fill_error_tests;
+mod cmd_fill_files_tests;
+mod cmd_fill_report_tests;
 mod cmd_fill_tests;
+mod cmd_vars_tests;